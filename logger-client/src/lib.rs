@@ -1,99 +1,1367 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::needless_pass_by_value)]
 
-use std::{error::Error, io::Write, os::unix::net::UnixStream, sync::Mutex};
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    fs::File,
+    io::{IoSlice, Write},
+    marker::PhantomData,
+    net::{Shutdown, TcpStream},
+    os::unix::{fs::PermissionsExt, net::UnixStream, process::CommandExt},
+    path::Path,
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{sync_channel, SyncSender},
+        Mutex, Once, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
-use log::{LevelFilter, Metadata, Record};
+use log::{
+    kv::{Key, Source, Value, VisitSource},
+    Level, LevelFilter, Metadata, Record, SetLoggerError,
+};
+use logger_wire::{
+    Header, COMPRESSED, CONTEXT_FRAME, CORRELATION_FRAME, KEY_VALUES_FRAME, LOG_FRAME,
+    PROCESS_NAME_FRAME, SAMPLING_FRAME, STATUS_FRAME, THREAD_NAME_FRAME,
+};
+
+thread_local! {
+    /// The level raised to by the innermost live [`VerbosityScope`] on this thread.
+    static SCOPE_LEVEL: Cell<Option<LevelFilter>> = const { Cell::new(None) };
+    /// The correlation id attached to records from this thread.
+    static CORRELATION_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// The connection, counted by [`CONNECTIONS`], [`CORRELATION_ID`] was reported on, `None` once
+    /// it has changed since.
+    static CORRELATION_REPORTED: Cell<Option<u64>> = const { Cell::new(Some(0)) };
+    /// The name given to this thread with `std::thread::Builder::name`, read on first use.
+    static THREAD_NAME: OnceCell<Option<String>> = const { OnceCell::new() };
+    /// The connection, counted by [`CONNECTIONS`], [`THREAD_NAME`] was reported on.
+    static THREAD_NAME_REPORTED: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// The logger installed by [`Logger::init`].
+static LOGGER: OnceLock<&'static Logger> = OnceLock::new();
+/// The number of times the logger has switched server with [`Logger::reconnect_to`].
+static CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+
+/// The number of records which could not be sent to the server.
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+/// The sequence number of the next frame, see [`Header::sequence`].
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// For each level, indexed by `level as usize - 1`, one in how many records are sent.
+static SAMPLING: [AtomicU32; 5] = [const { AtomicU32::new(1) }; 5];
+/// For each level, indexed by `level as usize - 1`, the number of enabled records logged.
+static SAMPLE_COUNTS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+/// The number of enabled records not sent due to sampling.
+static SAMPLED_OUT: AtomicU64 = AtomicU64::new(0);
+/// The number of times [`SAMPLING`] has been changed.
+static SAMPLING_CHANGES: AtomicU64 = AtomicU64::new(0);
+
+/// The number of recent records kept locally, none are kept when `0`.
+static RECENT_CAPACITY: AtomicUsize = AtomicUsize::new(0);
+/// The most recent records, oldest first, kept while [`RECENT_CAPACITY`] is not `0`.
+static RECENT: Mutex<VecDeque<RecentLog>> = Mutex::new(VecDeque::new());
+
+/// The key-value pairs describing this process, encoded once as the message of a
+/// [`CONTEXT_FRAME`], see [`Logger::set_context`].
+static CONTEXT: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// The length in bytes above which messages are compressed, none are when `usize::MAX`.
+static COMPRESS_ABOVE: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// The number of bytes of frames buffered before they are sent, each record is sent as it is
+/// logged when `0`.
+static BUFFER_CAPACITY: AtomicUsize = AtomicUsize::new(0);
+/// Starts the thread sending buffered frames every [`FLUSH_INTERVAL`].
+static FLUSHER: Once = Once::new();
+
+/// Starts the thread reporting dropped records every [`REPORT_INTERVAL`].
+static REPORTER: Once = Once::new();
+
+/// The queue of the thread sending records in the background, see [`Logger::send_in_background`].
+static BACKGROUND: OnceLock<SyncSender<Background>> = OnceLock::new();
+
+/// Whether the installed logger has been shut down with [`Logger::shutdown`].
+static SHUT_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// How long, in milliseconds, a spawned server is waited on to start listening, see
+/// [`Logger::set_spawn_timeout`].
+static SPAWN_TIMEOUT: AtomicU64 = AtomicU64::new(10_000);
+
+/// The name of the server binary spawned when the socket is not found.
+const SERVER_BINARY: &str = "logger-server";
+/// The number of times a lost connection to the server is retried before a record is dropped.
+const RECONNECT_ATTEMPTS: u32 = 3;
+/// The wait before the first retry of a lost connection, doubled before each following retry.
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(10);
+/// The longest buffered frames wait before they are sent.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+/// The longest the server goes without being told of newly dropped records, when no record is
+/// logged to tell it sooner.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+/// How long after failing to reconnect records are dropped without retrying, so logging is not
+/// slowed down by every record while the server is down.
+const RECONNECT_COOLDOWN: Duration = Duration::from_secs(1);
+/// How often the socket of a spawned server is tried while waiting for it to start listening.
+const SPAWN_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// The terminal emulators looked for on `PATH` by [`SpawnStrategy::default`], in order.
+const TERMINALS: [&str; 4] = ["gnome-terminal", "konsole", "xfce4-terminal", "xterm"];
+
+/// How the server is started when its socket is not found, see [`Logger::init_with_spawner`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpawnStrategy {
+    /// Runs the server in a new window of the named terminal emulator, such as `gnome-terminal`
+    /// or `xterm`.
+    Terminal(String),
+    /// Runs the server directly in the background with `--no-tui`, printing each log to
+    /// `<SOCKET>.out`.
+    Headless,
+    /// Never starts a server, failing with [`InitError::SocketNotFound`] instead.
+    None,
+}
+
+impl Default for SpawnStrategy {
+    /// Uses the terminal emulator named by `TERMINAL`, or else the first of `gnome-terminal`,
+    /// `konsole`, `xfce4-terminal` and `xterm` found on `PATH`, falling back to
+    /// [`SpawnStrategy::Headless`] when there is none, such as on a headless server or in CI.
+    fn default() -> Self {
+        std::env::var("TERMINAL")
+            .ok()
+            .filter(|terminal| !terminal.is_empty())
+            .or_else(|| {
+                TERMINALS
+                    .into_iter()
+                    .find(|terminal| on_path(terminal))
+                    .map(String::from)
+            })
+            .map_or(Self::Headless, Self::Terminal)
+    }
+}
+
+impl SpawnStrategy {
+    /// Starts the server listening on `socket`.
+    fn spawn(&self, socket: &str) -> Result<(), InitError> {
+        if *self == Self::None {
+            return Err(InitError::SocketNotFound(socket.to_string()));
+        }
+        // The server may be started within a shell in a new terminal, so its failure to start
+        // would otherwise only surface as a failure to connect.
+        if !on_path(SERVER_BINARY) {
+            return Err(InitError::ServerNotFound);
+        }
+
+        let mut command = if let Self::Terminal(terminal) = self {
+            // `gnome-terminal` takes the command after `--`, most others after `-e`.
+            let separator = if terminal.ends_with("gnome-terminal") {
+                "--"
+            } else {
+                "-e"
+            };
+            let mut command = Command::new(terminal);
+            command.args([
+                separator,
+                "sh",
+                "-c",
+                &format!("{SERVER_BINARY} --socket {socket}; exec bash"),
+            ]);
+            command
+        } else {
+            let output = File::create(format!("{socket}.out")).map_err(InitError::Spawn)?;
+            let mut command = Command::new(SERVER_BINARY);
+            // Without a terminal of its own the interface cannot be drawn, and drawing it would
+            // take over the terminal of this process.
+            command
+                .args(["--socket", socket, "--no-tui"])
+                .stdin(Stdio::null())
+                .stderr(output.try_clone().map_err(InitError::Spawn)?)
+                .stdout(output)
+                // Not interrupted along with this process.
+                .process_group(0);
+            command
+        };
+        let mut child = command.spawn().map_err(InitError::Spawn)?;
+        // Reaps the terminal or server once it exits.
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+/// An error initializing the logger.
+#[derive(Debug)]
+pub enum InitError {
+    /// The socket was not found and the server binary could not be found on `PATH` to spawn it.
+    ServerNotFound,
+    /// The socket was not found and spawning the server is disabled with [`SpawnStrategy::None`].
+    SocketNotFound(String),
+    /// Failed to spawn the server process.
+    Spawn(std::io::Error),
+    /// The spawned server did not start listening on the socket in time, see
+    /// [`Logger::set_spawn_timeout`].
+    ServerTimeout { socket: String, timeout: Duration },
+    /// Failed to connect to the server socket.
+    Connect(std::io::Error),
+    /// Failed to install the logger.
+    SetLogger(SetLoggerError),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ServerNotFound => write!(
+                f,
+                "`{SERVER_BINARY}` was not found on `PATH`, install it with `cargo install \
+                 logger-server --git https://github.com/JonathanWoollett-Light/multi-process-logger.git`"
+            ),
+            Self::SocketNotFound(socket) => write!(
+                f,
+                "the socket `{socket}` was not found and spawning the server is disabled"
+            ),
+            Self::Spawn(err) => write!(f, "failed to spawn the server: {err}"),
+            Self::ServerTimeout { socket, timeout } => write!(
+                f,
+                "the spawned server did not start listening on `{socket}` within {timeout:?}, it \
+                 may have failed to start"
+            ),
+            Self::Connect(err) => write!(f, "failed to connect to the server: {err}"),
+            Self::SetLogger(err) => write!(f, "failed to set the logger: {err}"),
+        }
+    }
+}
+
+impl Error for InitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ServerNotFound | Self::SocketNotFound(_) | Self::ServerTimeout { .. } => None,
+            Self::Spawn(err) | Self::Connect(err) => Some(err),
+            Self::SetLogger(err) => Some(err),
+        }
+    }
+}
+
+impl From<SetLoggerError> for InitError {
+    fn from(err: SetLoggerError) -> Self {
+        Self::SetLogger(err)
+    }
+}
+
+/// Returns whether `binary` is an executable file in a directory on `PATH`.
+fn on_path(binary: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| {
+            std::fs::metadata(dir.join(binary)).is_ok_and(|metadata| {
+                metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+            })
+        })
+    })
+}
+
+/// A record kept locally, see [`Logger::keep_recent`].
+#[derive(Debug, Clone)]
+pub struct RecentLog {
+    pub time: SystemTime,
+    pub level: Level,
+    pub message: String,
+}
+
+/// The transport to the server, which carries the same wire format either way.
+enum Stream {
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Stream {
+    /// Closes the stream, so the server sees the end of the connection.
+    fn shutdown(&self) -> std::io::Result<()> {
+        match self {
+            Self::Unix(stream) => stream.shutdown(Shutdown::Both),
+            Self::Tcp(stream) => stream.shutdown(Shutdown::Both),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Unix(stream) => stream.write(buf),
+            Self::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        match self {
+            Self::Unix(stream) => stream.write_vectored(bufs),
+            Self::Tcp(stream) => stream.write_vectored(bufs),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Unix(stream) => stream.flush(),
+            Self::Tcp(stream) => stream.flush(),
+        }
+    }
+}
+
+/// The connection to the server, buffering frames while [`BUFFER_CAPACITY`] is not `0`.
+struct Connection<S = Stream> {
+    stream: S,
+    /// Frames waiting to be sent, always empty while [`BUFFER_CAPACITY`] is `0`.
+    buffer: Vec<u8>,
+    /// The number of records in `buffer`.
+    buffered: u64,
+}
+
+impl<S> Connection<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+            buffered: 0,
+        }
+    }
+}
+
+impl<S: Write> Write for Connection<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if BUFFER_CAPACITY.load(Ordering::Relaxed) == 0 {
+            self.stream.write(buf)
+        } else {
+            self.buffer.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        if BUFFER_CAPACITY.load(Ordering::Relaxed) == 0 {
+            self.stream.write_vectored(bufs)
+        } else {
+            for buf in bufs {
+                self.buffer.extend_from_slice(buf);
+            }
+            Ok(bufs.iter().map(|buf| buf.len()).sum())
+        }
+    }
+
+    /// Sends the buffered frames, keeping them all buffered on failure.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.write_all(&self.buffer)?;
+        self.buffer.clear();
+        self.buffered = 0;
+        self.stream.flush()
+    }
+}
+
+/// Encodes the key-value pairs of a record for a [`KEY_VALUES_FRAME`].
+struct KeyValues(Vec<u8>);
+
+impl<'kvs> VisitSource<'kvs> for KeyValues {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        logger_wire::encode_key_value(&mut self.0, key.as_str(), &value.to_string());
+        Ok(())
+    }
+}
+
+/// A request to the thread started by [`Logger::send_in_background`].
+enum Background {
+    /// Sends the encoded frames of a record.
+    Frames(Vec<u8>),
+    /// Sends the buffered frames, then signals the sender.
+    Flush(SyncSender<()>),
+}
+
+/// Returns whether `err` is from the server having closed the connection.
+fn closed(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::NotConnected
+    )
+}
+
+/// Returns whether `target` is the `host:port` address of a server listening over TCP rather
+/// than the path of a Unix socket.
+fn is_tcp(target: &str) -> bool {
+    target.rsplit_once(':').is_some_and(|(host, port)| {
+        !host.is_empty() && !host.contains('/') && port.parse::<u16>().is_ok()
+    })
+}
 
 pub struct Logger {
-    stream: Mutex<UnixStream>,
+    connection: Mutex<Connection>,
+    /// The path of the socket, or the TCP address, `connection` is connected to.
+    socket: Mutex<String>,
     log_level: LevelFilter,
+    /// The value of [`DROPPED`] last reported to the server.
+    reported_dropped: AtomicU64,
+    /// The value of [`SAMPLING_CHANGES`] last reported to the server.
+    reported_sampling: AtomicU64,
+    /// When a lost connection may next be retried, after failing to reconnect.
+    retry_at: Mutex<Option<Instant>>,
+}
+
+/// Restores the verbosity of the current thread when dropped.
+///
+/// See [`Logger::verbosity_scope`].
+#[must_use = "the verbosity is restored as soon as the scope is dropped"]
+pub struct VerbosityScope {
+    previous: Option<LevelFilter>,
+    // The scope restores thread-local state so must be dropped on the thread that created it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for VerbosityScope {
+    fn drop(&mut self) {
+        SCOPE_LEVEL.with(|level| level.set(self.previous));
+    }
 }
 
 impl Logger {
-    /// Initializes the logger.
+    /// Initializes the logger, connecting with [`Logger::new`] then installing it with
+    /// [`Logger::install`].
+    ///
+    /// `socket` is the path of the Unix socket of the server, or the `host:port` address of a
+    /// server listening over TCP with `--tcp`, such as `10.0.0.2:9000`. Spawns a new server
+    /// process with [`SpawnStrategy::default`] if it cannot find the socket, a server is never
+    /// spawned for a TCP address.
+    ///
+    /// # Errors
+    ///
+    /// See [`Logger::init_with_spawner`].
+    pub fn init(socket: &str, log_level: LevelFilter) -> Result<(), InitError> {
+        Self::init_with_spawner(socket, log_level, SpawnStrategy::default())
+    }
+
+    /// Initializes the logger, spawning a new server process with `spawner` if it cannot find the
+    /// socket.
+    ///
+    /// ```ignore
+    /// Logger::init_with_spawner("/tmp/my-unix-socket", LevelFilter::Debug, SpawnStrategy::None)?;
+    /// ```
     ///
-    /// Spawns a new server process if it cannot find the socket.
+    /// # Errors
+    ///
+    /// See [`Logger::with_spawner`] and [`Logger::install`].
+    pub fn init_with_spawner(
+        socket: &str,
+        log_level: LevelFilter,
+        spawner: SpawnStrategy,
+    ) -> Result<(), InitError> {
+        Self::with_spawner(socket, log_level, spawner)?.install()
+    }
+
+    /// Connects to the server listening on `socket`, as [`Logger::init`] does, without installing
+    /// the logger, so it can be wrapped by another `log::Log` implementation, such as one also
+    /// writing to stderr, or used directly.
+    ///
+    /// [`Logger::send_in_background`] and [`Logger::reconnect_to`] only apply to the installed
+    /// logger. A logger which is not installed sends records from the threads logging them and,
+    /// with [`Logger::buffer_writes`], only sends buffered records once the buffer is full or it
+    /// is flushed.
+    ///
+    /// ```ignore
+    /// let logger = Logger::new("/tmp/my-unix-socket", LevelFilter::Debug)?;
+    /// log::Log::log(&logger, &record);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`Logger::with_spawner`].
+    pub fn new(socket: &str, log_level: LevelFilter) -> Result<Self, InitError> {
+        Self::with_spawner(socket, log_level, SpawnStrategy::default())
+    }
+
+    /// Connects to the server listening on `socket` without installing the logger, spawning a new
+    /// server process with `spawner` if it cannot find the socket, see [`Logger::new`].
     ///
     /// # Errors
     ///
     /// When failing:
+    /// - To find the socket with [`SpawnStrategy::None`] ([`InitError::SocketNotFound`]).
+    /// - To find the server binary on `PATH` ([`InitError::ServerNotFound`]).
     /// - To spawn the new server process.
+    /// - For the new server process to start listening ([`InitError::ServerTimeout`]).
     /// - To socket to the server unix socket.
-    /// - [`log::set_boxed_logger`].
-    pub fn init(socket: &str, log_level: LevelFilter) -> Result<(), Box<dyn Error>> {
+    pub fn with_spawner(
+        socket: &str,
+        log_level: LevelFilter,
+        spawner: SpawnStrategy,
+    ) -> Result<Self, InitError> {
         // If socket doesn't exist, boot new server
-        if !std::path::Path::new(socket).exists() {
-            std::process::Command::new("gnome-terminal")
-                .args([
-                    "&",
-                    "disown",
-                    "--",
-                    "sh",
-                    "-c",
-                    &format!("logger-server --socket {socket}; exec bash"),
-                    // &format!("cargo run --bin logger-server -- --socket {socket}; exec bash"),
-                ])
-                .spawn()?;
-            // Wait for process to start
-            std::thread::sleep(std::time::Duration::from_secs(5));
-        }
-
-        let logger = Self {
-            stream: Mutex::new(UnixStream::connect(socket)?),
-            log_level,
+        let stream = if !is_tcp(socket) && !Path::new(socket).exists() {
+            spawner.spawn(socket)?;
+            wait_for_server(socket)?
+        } else {
+            connect(socket).map_err(InitError::Connect)?
         };
-        log::set_boxed_logger(Box::new(logger))?;
+
+        Ok(Self {
+            connection: Mutex::new(Connection::new(stream)),
+            socket: Mutex::new(socket.to_string()),
+            log_level,
+            reported_dropped: AtomicU64::new(0),
+            reported_sampling: AtomicU64::new(0),
+            retry_at: Mutex::new(None),
+        })
+    }
+
+    /// Installs the logger as the logger of the `log` crate, enabling records up to the level it
+    /// was created with.
+    ///
+    /// # Errors
+    ///
+    /// When a logger is already installed ([`InitError::SetLogger`]).
+    pub fn install(self) -> Result<(), InitError> {
+        let log_level = self.log_level;
+        let logger = Box::leak(Box::new(self));
+        log::set_logger(logger)?;
         log::set_max_level(log_level);
+        let _ = LOGGER.set(logger);
+
+        REPORTER.call_once(|| {
+            std::thread::spawn(|| loop {
+                std::thread::sleep(REPORT_INTERVAL);
+                if SHUT_DOWN.load(Ordering::Relaxed) {
+                    break;
+                }
+                if let Some(logger) = LOGGER.get() {
+                    logger.report_dropped(&mut *logger.connection.lock().unwrap());
+                }
+            });
+        });
         Ok(())
     }
+
+    /// Returns whether this is the installed logger.
+    fn installed(&self) -> bool {
+        LOGGER
+            .get()
+            .is_some_and(|logger| std::ptr::eq(*logger, self))
+    }
+
+    /// Switches the installed logger to the server listening on `socket`, closing its connection
+    /// to the current server.
+    ///
+    /// Records logged during the switch wait for it to finish and are sent to the new server, so
+    /// none are lost or split between servers. The new server is told of the dropped records,
+    /// sampling rates and correlation ids as it would be by a new process.
+    ///
+    /// # Errors
+    ///
+    /// When the logger is not initialized or failing to connect to `socket`, in which case the
+    /// current connection is kept.
+    pub fn reconnect_to(socket: &str) -> std::io::Result<()> {
+        let logger = LOGGER.get().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "the logger is not initialized",
+            )
+        })?;
+        // Connects before taking the lock so logging is only blocked for the switch itself.
+        let new_stream = connect(socket)?;
+
+        let mut connection = logger.connection.lock().unwrap();
+        // Buffered frames which cannot be sent to the current server are sent to the new one.
+        let _ = connection.flush();
+        logger.switch(&mut connection, new_stream);
+        *logger.socket.lock().unwrap() = socket.to_string();
+        Ok(())
+    }
+
+    /// Replaces the stream of `connection` with `new_stream`, keeping any buffered frames.
+    fn switch(&self, connection: &mut Connection, new_stream: Stream) {
+        connection.stream = new_stream;
+        CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+        // The new server only knows the defaults, no records dropped and none sampled out.
+        self.reported_dropped.store(0, Ordering::Relaxed);
+        self.reported_sampling.store(0, Ordering::Relaxed);
+    }
+
+    /// Reconnects `connection` to the socket after the server closed it, such as when it
+    /// restarted, retrying with a backoff. Returns whether it reconnected.
+    fn reconnect(&self, connection: &mut Connection) -> bool {
+        if SHUT_DOWN.load(Ordering::Relaxed) && self.installed() {
+            return false;
+        }
+        let mut retry_at = self.retry_at.lock().unwrap();
+        if retry_at.is_some_and(|retry_at| Instant::now() < retry_at) {
+            return false;
+        }
+        let socket = self.socket.lock().unwrap().clone();
+        let mut backoff = RECONNECT_BACKOFF;
+        for _ in 0..RECONNECT_ATTEMPTS {
+            std::thread::sleep(backoff);
+            backoff *= 2;
+            if let Ok(new_stream) = connect(&socket) {
+                self.switch(connection, new_stream);
+                *retry_at = None;
+                return true;
+            }
+        }
+        *retry_at = Some(Instant::now() + RECONNECT_COOLDOWN);
+        false
+    }
+
+    /// Lets the server know of the records dropped since it was last told.
+    fn report_dropped(&self, stream: &mut impl Write) {
+        let dropped = DROPPED.load(Ordering::Relaxed);
+        if dropped != self.reported_dropped.load(Ordering::Relaxed)
+            && write_frame(stream, STATUS_FRAME, Level::Warn, &dropped.to_le_bytes()).is_ok()
+        {
+            self.reported_dropped.store(dropped, Ordering::Relaxed);
+        }
+    }
+
+    /// Sends `record` holding `message`, preceded by whatever the server has yet to be told of.
+    fn send(&self, stream: &mut impl Write, record: &Record, message: &str) -> std::io::Result<()> {
+        // Let the server know records have been lost before sending any more.
+        self.report_dropped(stream);
+
+        // Let the server know the name of the thread once per connection, even when it has none
+        // so the name of an exited thread with the same id is not shown for it.
+        let connection = CONNECTIONS.load(Ordering::Relaxed);
+        if THREAD_NAME_REPORTED.with(Cell::get) != Some(connection) {
+            let reported = THREAD_NAME.with(|name| {
+                let name = name.get_or_init(|| std::thread::current().name().map(String::from));
+                let name = name.as_deref().unwrap_or_default();
+                write_frame(stream, THREAD_NAME_FRAME, Level::Info, name.as_bytes()).is_ok()
+            });
+            if reported {
+                THREAD_NAME_REPORTED.with(|reported| reported.set(Some(connection)));
+            }
+        }
+
+        // Let the server know which correlation id the record is attached to, once it has
+        // changed or once the thread has an id and the logger has switched server.
+        let reported = CORRELATION_REPORTED.with(Cell::get);
+        let has_id = CORRELATION_ID.with(|id| id.borrow().is_some());
+        if reported.is_none() || (has_id && reported != Some(connection)) {
+            let id = CORRELATION_ID.with(|id| id.borrow().clone().unwrap_or_default());
+            if write_frame(stream, CORRELATION_FRAME, Level::Info, id.as_bytes()).is_ok() {
+                CORRELATION_REPORTED.with(|reported| reported.set(Some(connection)));
+            }
+        }
+
+        // Let the server know the key-value pairs of the record, only if it has any.
+        if record.key_values().count() != 0 {
+            let mut key_values = KeyValues(Vec::new());
+            // Visiting only fails when the visitor does, which it never does.
+            let _ = record.key_values().visit(&mut key_values);
+            write_frame(stream, KEY_VALUES_FRAME, record.level(), &key_values.0)?;
+        }
+
+        write_record(stream, record, message.as_bytes())?;
+
+        // Let the server know which levels are sampled, once it knows of this process.
+        let changes = SAMPLING_CHANGES.load(Ordering::Relaxed);
+        if changes != self.reported_sampling.load(Ordering::Relaxed) {
+            let rates = SAMPLING
+                .iter()
+                .flat_map(|rate| rate.load(Ordering::Relaxed).to_le_bytes())
+                .collect::<Vec<_>>();
+            if write_frame(stream, SAMPLING_FRAME, Level::Info, &rates).is_ok() {
+                self.reported_sampling.store(changes, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets what the server was told by the frames of a record which was never sent, so it is
+    /// told again.
+    fn forget_reported(&self) {
+        THREAD_NAME_REPORTED.with(|reported| reported.set(None));
+        CORRELATION_REPORTED.with(|reported| reported.set(None));
+        self.reported_sampling.store(0, Ordering::Relaxed);
+    }
+
+    /// Writes a record to `connection` with `write`, writing it again once reconnected if the
+    /// server closed the connection, then sends the buffered frames once the buffer is full.
+    fn deliver(
+        &self,
+        connection: &mut Connection,
+        mut write: impl FnMut(&mut Connection) -> std::io::Result<()>,
+    ) {
+        let mut sent = write(connection);
+        // The server closed the connection, it may have restarted.
+        if sent.as_ref().is_err_and(closed) && self.reconnect(connection) {
+            sent = write(connection);
+        }
+        if sent.is_err() {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        } else if !connection.buffer.is_empty() {
+            connection.buffered += 1;
+        }
+
+        if !connection.buffer.is_empty()
+            && connection.buffer.len() >= BUFFER_CAPACITY.load(Ordering::Relaxed)
+        {
+            self.flush_buffer(connection);
+        }
+    }
+
+    /// Sends the buffered frames of `connection`, reconnecting if the server closed it. The
+    /// buffered records are dropped if they still cannot be sent.
+    fn flush_buffer(&self, connection: &mut Connection) {
+        let mut flushed = connection.flush();
+        if flushed.as_ref().is_err_and(closed) && self.reconnect(connection) {
+            flushed = connection.flush();
+        }
+        if flushed.is_err() {
+            DROPPED.fetch_add(connection.buffered, Ordering::Relaxed);
+            connection.buffer.clear();
+            connection.buffered = 0;
+        }
+    }
+
+    /// Buffers up to `capacity` bytes of records before sending them to the server, so a burst
+    /// of records is sent in a few writes rather than one each. A capacity of `0`, the default,
+    /// sends each record as it is logged.
+    ///
+    /// Buffered records are sent once the buffer is full, at least every 100ms and when the
+    /// logger is flushed with `log::logger().flush()`, which should be done before exiting.
+    ///
+    /// ```ignore
+    /// Logger::buffer_writes(64 * 1024);
+    /// ```
+    pub fn buffer_writes(capacity: usize) {
+        if let Some(logger) = LOGGER.get() {
+            // The capacity is only changed under the lock, so the buffer is always empty while it
+            // is `0` and records are never sent ahead of those already buffered.
+            let mut connection = logger.connection.lock().unwrap();
+            BUFFER_CAPACITY.store(capacity, Ordering::Relaxed);
+            if connection.buffer.len() >= capacity {
+                logger.flush_buffer(&mut connection);
+            }
+        } else {
+            BUFFER_CAPACITY.store(capacity, Ordering::Relaxed);
+        }
+
+        if capacity != 0 {
+            FLUSHER.call_once(|| {
+                std::thread::spawn(|| loop {
+                    std::thread::sleep(FLUSH_INTERVAL);
+                    if let Some(logger) = LOGGER.get() {
+                        let mut connection = logger.connection.lock().unwrap();
+                        if !connection.buffer.is_empty() {
+                            logger.flush_buffer(&mut connection);
+                        }
+                    }
+                });
+            });
+        }
+    }
+
+    /// Sends records to the server from a background thread rather than the threads logging them,
+    /// so logging never waits on a slow or stalled server. Up to `capacity` records wait to be
+    /// sent, once that many are waiting further records are dropped rather than waited on,
+    /// counted by [`Logger::dropped_records`].
+    ///
+    /// Once enabled records are always sent in the background, later calls have no effect.
+    ///
+    /// ```ignore
+    /// Logger::send_in_background(10_000);
+    /// ```
+    pub fn send_in_background(capacity: usize) {
+        BACKGROUND.get_or_init(|| {
+            // A capacity of `0` would only queue records while the thread is waiting for them.
+            let (sender, receiver) = sync_channel(capacity.max(1));
+            std::thread::spawn(move || {
+                for request in receiver {
+                    let Some(logger) = LOGGER.get() else {
+                        continue;
+                    };
+                    let mut connection = logger.connection.lock().unwrap();
+                    match request {
+                        Background::Frames(frames) => {
+                            logger.deliver(&mut connection, |connection| {
+                                connection.write_all(&frames)
+                            });
+                        }
+                        Background::Flush(done) => {
+                            logger.flush_buffer(&mut connection);
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            });
+            sender
+        });
+    }
+
+    /// Sends every record logged so far and closes the connection of the installed logger, such as
+    /// before exiting. Records logged afterwards are dropped, counted by
+    /// [`Logger::dropped_records`].
+    ///
+    /// `log` never drops the installed logger, so without a shutdown or flush records buffered
+    /// with [`Logger::buffer_writes`] or queued with [`Logger::send_in_background`] are lost on
+    /// exit. A logger which is not installed is flushed when it is dropped.
+    ///
+    /// ```ignore
+    /// Logger::shutdown();
+    /// ```
+    pub fn shutdown() {
+        let Some(logger) = LOGGER.get() else {
+            return;
+        };
+        // Set first so records logged during the flush are not sent after the connection closes.
+        if SHUT_DOWN.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        log::Log::flush(*logger);
+        let _ = logger.connection.lock().unwrap().stream.shutdown();
+    }
+
+    /// Waits up to `timeout` for a server spawned by [`Logger::init`] to start listening, 10
+    /// seconds by default. Records are sent as soon as it is listening, the timeout only bounds
+    /// how long a server which failed to start is waited on.
+    ///
+    /// ```ignore
+    /// Logger::set_spawn_timeout(Duration::from_secs(30));
+    /// ```
+    pub fn set_spawn_timeout(timeout: Duration) {
+        let millis = u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX);
+        SPAWN_TIMEOUT.store(millis, Ordering::Relaxed);
+    }
+
+    /// Compresses the messages of records longer than `threshold` bytes before sending them, or
+    /// none when `None`, the default. Compression only pays off for large messages, such as JSON
+    /// documents, so small messages are always sent as they are.
+    ///
+    /// ```ignore
+    /// Logger::compress_above(Some(4096));
+    /// ```
+    pub fn compress_above(threshold: Option<usize>) {
+        COMPRESS_ABOVE.store(threshold.unwrap_or(usize::MAX), Ordering::Relaxed);
+    }
+
+    /// Describes this process with `fields`, such as its host and version, which the server shows
+    /// alongside the process rather than on each record. The fields are encoded once and sent
+    /// once per connection, so they cost nothing per record.
+    ///
+    /// Connections opened afterwards describe the process with them, so they should be set before
+    /// [`Logger::init`].
+    ///
+    /// ```ignore
+    /// Logger::set_context(&[("host", "web-1"), ("version", env!("CARGO_PKG_VERSION"))]);
+    /// ```
+    pub fn set_context(fields: &[(&str, &str)]) {
+        let mut message = Vec::new();
+        for (key, value) in fields {
+            logger_wire::encode_key_value(&mut message, key, value);
+        }
+        *CONTEXT.lock().unwrap() = message;
+    }
+
+    /// Returns the path of the socket, or the TCP address, of the server the installed logger
+    /// sends records to.
+    #[must_use]
+    pub fn target() -> Option<String> {
+        LOGGER
+            .get()
+            .map(|logger| logger.socket.lock().unwrap().clone())
+    }
+
+    /// Returns the number of records which could not be sent to the server, including those
+    /// logged while the server was down and could not be reconnected to.
+    #[must_use]
+    pub fn dropped_records() -> u64 {
+        DROPPED.load(Ordering::Relaxed)
+    }
+
+    /// Sends only one in `one_in` enabled records at `level`, counting the rest as sampled out
+    /// rather than dropped. A rate of `0` or `1` sends every record.
+    ///
+    /// Sampling is deterministic, the first of every `one_in` records at `level` is sent, so of `n`
+    /// records exactly `n.div_ceil(one_in)` are sent. The rates are reported to the server so
+    /// viewers know which levels are sampled.
+    ///
+    /// ```ignore
+    /// Logger::sample(Level::Trace, 100);
+    /// ```
+    pub fn sample(level: Level, one_in: u32) {
+        SAMPLING[level as usize - 1].store(one_in.max(1), Ordering::Relaxed);
+        SAMPLING_CHANGES.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of enabled records which were not sent due to sampling.
+    #[must_use]
+    pub fn sampled_out_records() -> u64 {
+        SAMPLED_OUT.load(Ordering::Relaxed)
+    }
+
+    /// Keeps the last `capacity` enabled records in memory to be read back with
+    /// [`Logger::recent_logs`], for example to include in a crash report. A capacity of `0`, the
+    /// default, keeps none and discards any already kept.
+    ///
+    /// Records are kept whether or not they are sampled out or reach the server.
+    ///
+    /// ```ignore
+    /// Logger::keep_recent(100);
+    /// ```
+    pub fn keep_recent(capacity: usize) {
+        let mut recent = RECENT.lock().unwrap();
+        RECENT_CAPACITY.store(capacity, Ordering::Relaxed);
+        let excess = recent.len().saturating_sub(capacity);
+        recent.drain(..excess);
+        recent.shrink_to(capacity);
+    }
+
+    /// Returns the records kept by [`Logger::keep_recent`], oldest first.
+    #[must_use]
+    pub fn recent_logs() -> Vec<RecentLog> {
+        RECENT.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Attaches the correlation id `id` to the records subsequently logged from the current thread,
+    /// so a request can be followed across processes and threads in the server. `None`, or an
+    /// empty id, detaches it.
+    ///
+    /// ```ignore
+    /// Logger::set_correlation_id(Some("request-42"));
+    /// ```
+    pub fn set_correlation_id(id: Option<&str>) {
+        CORRELATION_ID.with(|current| {
+            *current.borrow_mut() = id.filter(|id| !id.is_empty()).map(String::from);
+        });
+        CORRELATION_REPORTED.with(|reported| reported.set(None));
+    }
+
+    /// Enables records up to `level` on the current thread until the returned guard is dropped.
+    ///
+    /// Scopes nest, an inner scope never lowers the verbosity set by an outer scope.
+    ///
+    /// `log` checks records against its global max level before they reach the logger, so if
+    /// `level` is more verbose than the global max level the global max level is raised to it. It
+    /// is not lowered when the guard is dropped, records from other threads are still filtered by
+    /// the level given to [`Logger::init`].
+    ///
+    /// ```ignore
+    /// let _scope = Logger::verbosity_scope(Level::Trace);
+    /// log::trace!("sent even though the logger was initialized with `LevelFilter::Info`");
+    /// ```
+    pub fn verbosity_scope(level: Level) -> VerbosityScope {
+        let level = level.to_level_filter();
+        if log::max_level() < level {
+            log::set_max_level(level);
+        }
+        let previous = SCOPE_LEVEL.with(|scope| {
+            let previous = scope.get();
+            scope.set(Some(previous.map_or(level, |previous| previous.max(level))));
+            previous
+        });
+        VerbosityScope {
+            previous,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// Connects to the server listening on `socket`, see [`Logger::init`], opening the connection with
+/// the handshake followed by the name and context of this process.
+fn connect(socket: &str) -> std::io::Result<Stream> {
+    let mut stream = if is_tcp(socket) {
+        let stream = TcpStream::connect(socket)?;
+        // Frames are sent as they are logged, as over a Unix socket, rather than held back to
+        // be coalesced.
+        stream.set_nodelay(true)?;
+        Stream::Tcp(stream)
+    } else {
+        Stream::Unix(UnixStream::connect(socket)?)
+    };
+    stream.write_all(&logger_wire::handshake())?;
+    write_frame(
+        &mut stream,
+        PROCESS_NAME_FRAME,
+        Level::Info,
+        process_name().as_bytes(),
+    )?;
+    let context = CONTEXT.lock().unwrap();
+    if !context.is_empty() {
+        write_frame(&mut stream, CONTEXT_FRAME, Level::Info, &context)?;
+    }
+    Ok(stream)
+}
+
+/// Connects to the server spawned to listen on `socket` once it is listening, trying every
+/// [`SPAWN_POLL_INTERVAL`] until [`SPAWN_TIMEOUT`] has passed.
+fn wait_for_server(socket: &str) -> Result<Stream, InitError> {
+    let timeout = Duration::from_millis(SPAWN_TIMEOUT.load(Ordering::Relaxed));
+    let start = Instant::now();
+    loop {
+        // The server binds the socket before anything else, so it is connected to as soon as it
+        // exists.
+        if let Ok(stream) = connect(socket) {
+            return Ok(stream);
+        }
+        if start.elapsed() >= timeout {
+            return Err(InitError::ServerTimeout {
+                socket: socket.to_string(),
+                timeout,
+            });
+        }
+        std::thread::sleep(SPAWN_POLL_INTERVAL);
+    }
+}
+
+/// Returns the name of this process, as given by `/proc/self/comm` or else the file name of its
+/// executable, read once.
+fn process_name() -> &'static str {
+    static PROCESS_NAME: OnceLock<String> = OnceLock::new();
+    PROCESS_NAME.get_or_init(|| {
+        process_name_from(
+            || std::fs::read_to_string("/proc/self/comm"),
+            std::env::current_exe,
+        )
+    })
+}
+
+/// Returns the name of a process given by `read_comm`, its `comm` as read from `/proc`, or else the
+/// file name of the executable given by `current_exe`, or an empty name when neither is known.
+fn process_name_from(
+    read_comm: impl FnOnce() -> std::io::Result<String>,
+    current_exe: impl FnOnce() -> std::io::Result<std::path::PathBuf>,
+) -> String {
+    read_comm()
+        .map(|comm| comm.trim_end().to_string())
+        .ok()
+        .or_else(|| {
+            let exe = current_exe().ok()?;
+            Some(exe.file_name()?.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the header of a frame of `kind` whose message is `length` bytes, with no source.
+fn header(kind: u8, level: Level, length: usize) -> Header {
+    let system_time = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap();
+
+    Header {
+        secs: system_time.as_secs(),
+        nanos: system_time.subsec_nanos(),
+        sequence: SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        pid: nix::unistd::Pid::this().as_raw(),
+        pthread: nix::sys::pthread::pthread_self(),
+        length: length as u64,
+        target_length: 0,
+        module_path_length: 0,
+        file_length: 0,
+        line: 0,
+        level: level as u8,
+        kind,
+        flags: 0,
+    }
+}
+
+/// Writes a frame holding `message`.
+fn write_frame(
+    stream: &mut impl Write,
+    kind: u8,
+    level: Level,
+    message: &[u8],
+) -> std::io::Result<()> {
+    let header = header(kind, level, message.len()).encode();
+    write_all_vectored(stream, &mut [IoSlice::new(&header), IoSlice::new(message)])
+}
+
+/// Writes a log frame holding `message` along with the target, module path, file and line of
+/// `record`, compressed when `message` is longer than [`COMPRESS_ABOVE`].
+fn write_record(stream: &mut impl Write, record: &Record, message: &[u8]) -> std::io::Result<()> {
+    // A source too long for the header is left out, which no real target, module path or file
+    // comes near.
+    fn source(source: &str) -> (&str, u16) {
+        u16::try_from(source.len()).map_or(("", 0), |length| (source, length))
+    }
+    let (target, target_length) = source(record.target());
+    let (module_path, module_path_length) = source(record.module_path().unwrap_or_default());
+    let (file, file_length) = source(record.file().unwrap_or_default());
+    let header = Header {
+        target_length,
+        module_path_length,
+        file_length,
+        line: record.line().unwrap_or(0),
+        ..header(
+            LOG_FRAME,
+            record.level(),
+            target.len() + module_path.len() + file.len() + message.len(),
+        )
+    };
+
+    if message.len() > COMPRESS_ABOVE.load(Ordering::Relaxed) {
+        let uncompressed = [
+            target.as_bytes(),
+            module_path.as_bytes(),
+            file.as_bytes(),
+            message,
+        ];
+        let compressed = logger_wire::compress(&uncompressed.concat());
+        // Incompressible messages, such as those already compressed, are sent as they are.
+        let length = compressed.len() as u64;
+        if length < header.length {
+            let header = Header {
+                length,
+                flags: COMPRESSED,
+                ..header
+            }
+            .encode();
+            return write_all_vectored(
+                stream,
+                &mut [IoSlice::new(&header), IoSlice::new(&compressed)],
+            );
+        }
+    }
+
+    let header = header.encode();
+    write_all_vectored(
+        stream,
+        &mut [
+            IoSlice::new(&header),
+            IoSlice::new(target.as_bytes()),
+            IoSlice::new(module_path.as_bytes()),
+            IoSlice::new(file.as_bytes()),
+            IoSlice::new(message),
+        ],
+    )
 }
 
-#[repr(C)]
-struct LogData {
-    secs: u64,
-    nanos: u32,
-    pid: nix::unistd::Pid,
-    pthread: nix::sys::pthread::Pthread,
-    length: usize,
-    level: log::Level,
+/// Writes `bufs` in as few writes as possible without copying them into a single buffer.
+fn write_all_vectored(stream: &mut impl Write, mut bufs: &mut [IoSlice]) -> std::io::Result<()> {
+    while !bufs.is_empty() {
+        match stream.write_vectored(bufs) {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
 }
 
 impl log::Log for Logger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= self.log_level
+        let log_level = SCOPE_LEVEL
+            .with(Cell::get)
+            .map_or(self.log_level, |scope| scope.max(self.log_level));
+        metadata.level() <= log_level
     }
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            let system_time = std::time::SystemTime::now()
-                .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                .unwrap();
-
-            let message = record.args().to_string();
-            let message_bytes = message.as_bytes();
-
-            let fixed = LogData {
-                secs: system_time.as_secs(),
-                nanos: system_time.subsec_nanos(),
-                pid: nix::unistd::Pid::this(),
-                pthread: nix::sys::pthread::pthread_self(),
-                length: message_bytes.len(),
-                level: record.level(),
-            };
-            let array =
-                unsafe { std::mem::transmute::<_, [u8; std::mem::size_of::<LogData>()]>(fixed) };
+            if SHUT_DOWN.load(Ordering::Relaxed) && self.installed() {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
 
-            let bytes = array
-                .into_iter()
-                .chain(message_bytes.iter().copied())
-                .collect::<Vec<_>>();
+            // Kept before sampling so the local history is complete.
+            let message = (RECENT_CAPACITY.load(Ordering::Relaxed) != 0).then(|| {
+                let message = record.args().to_string();
+                let mut recent = RECENT.lock().unwrap();
+                // The capacity is only changed under the lock, it may have changed since.
+                let capacity = RECENT_CAPACITY.load(Ordering::Relaxed);
+                if capacity != 0 {
+                    if recent.len() >= capacity {
+                        recent.pop_front();
+                    }
+                    recent.push_back(RecentLog {
+                        time: SystemTime::now(),
+                        level: record.level(),
+                        message: message.clone(),
+                    });
+                }
+                message
+            });
+
+            let level = record.level() as usize - 1;
+            let one_in = u64::from(SAMPLING[level].load(Ordering::Relaxed));
+            if SAMPLE_COUNTS[level].fetch_add(1, Ordering::Relaxed) % one_in != 0 {
+                SAMPLED_OUT.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
+            let message = message.unwrap_or_else(|| record.args().to_string());
+
+            if let Some(background) = BACKGROUND.get().filter(|_| self.installed()) {
+                // The frames are encoded on this thread, which they describe, and sent by the
+                // background thread.
+                let mut frames = Vec::new();
+                // Writing to a `Vec` never fails.
+                let _ = self.send(&mut frames, record, &message);
+                if background.try_send(Background::Frames(frames)).is_err() {
+                    DROPPED.fetch_add(1, Ordering::Relaxed);
+                    self.forget_reported();
+                }
+                return;
+            }
 
-            self.stream.lock().unwrap().write_all(&bytes).unwrap();
+            self.deliver(&mut self.connection.lock().unwrap(), |connection| {
+                self.send(connection, record, &message)
+            });
         }
     }
 
     fn flush(&self) {
-        self.stream.lock().unwrap().flush().unwrap();
+        if let Some(background) = BACKGROUND.get().filter(|_| self.installed()) {
+            // Waits for the background thread to send the records queued before the flush.
+            let (done, flushed) = sync_channel(1);
+            if background.send(Background::Flush(done)).is_ok() {
+                let _ = flushed.recv();
+            }
+            return;
+        }
+        self.flush_buffer(&mut self.connection.lock().unwrap());
+    }
+}
+
+impl Drop for Logger {
+    /// Sends the records still buffered, the installed logger is never dropped, see
+    /// [`Logger::shutdown`].
+    fn drop(&mut self) {
+        log::Log::flush(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn process_name_fallbacks() {
+        fn unreadable<T>() -> std::io::Result<T> {
+            Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+        }
+
+        assert_eq!(
+            process_name_from(|| Ok(String::from("worker\n")), || unreachable!()),
+            "worker"
+        );
+        assert_eq!(
+            process_name_from(unreadable, || Ok(std::path::PathBuf::from("/usr/bin/app"))),
+            "app"
+        );
+        // Without `/proc` and an executable, or one without a file name, the name is empty.
+        assert_eq!(process_name_from(unreadable, unreadable), "");
+        assert_eq!(
+            process_name_from(unreadable, || Ok(std::path::PathBuf::from("/"))),
+            ""
+        );
+    }
+
+    /// Decodes the frame `bytes`, returning its header and its message decompressed.
+    fn decode_frame(bytes: &[u8]) -> (Header, Vec<u8>) {
+        let (header, message) = bytes.split_at(Header::LEN);
+        let header = Header::decode(header).unwrap();
+        assert_eq!(message.len() as u64, header.length);
+        let message = if header.flags & COMPRESSED == 0 {
+            message.to_vec()
+        } else {
+            logger_wire::decompress(message, usize::MAX).unwrap()
+        };
+        (header, message)
+    }
+
+    /// Returns `len` bytes which deflate cannot make smaller.
+    fn incompressible(len: usize) -> Vec<u8> {
+        // xorshift, any sequence without repeats will do.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state.to_le_bytes()[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn write_record_compression() {
+        // Other tests only log messages far shorter than the threshold.
+        COMPRESS_ABOVE.store(1024, Ordering::Relaxed);
+        let record = Record::builder().level(Level::Info).target("t").build();
+        for (message, compressed) in [
+            (Vec::new(), false),
+            (b"short".to_vec(), false),
+            (b"repeated ".repeat(1000), true),
+            (incompressible(4096), false),
+        ] {
+            let mut bytes = Vec::new();
+            write_record(&mut bytes, &record, &message).unwrap();
+            let (header, decoded) = decode_frame(&bytes);
+            assert_eq!(header.flags & COMPRESSED != 0, compressed);
+            assert_eq!(header.target_length, 1);
+            assert_eq!(decoded, [b"t".as_slice(), &message].concat());
+        }
+        COMPRESS_ABOVE.store(usize::MAX, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn spawn_disabled() {
+        let socket = std::env::temp_dir().join(format!("mp-logger-missing-{}", std::process::id()));
+        let socket = socket.to_str().unwrap();
+        let result = Logger::with_spawner(socket, LevelFilter::Info, SpawnStrategy::None);
+        assert!(matches!(result, Err(InitError::SocketNotFound(missing)) if missing == socket));
+        // No server was started to create the socket, nor its output.
+        assert!(!Path::new(socket).exists());
+        assert!(!Path::new(&format!("{socket}.out")).exists());
+    }
+
+    /// Counts the writes made to it.
+    #[derive(Default)]
+    struct CountingStream {
+        writes: usize,
+        bytes: usize,
+    }
+
+    impl Write for CountingStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.bytes += buf.len();
+            Ok(buf.len())
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+            self.writes += 1;
+            let len = bufs.iter().map(|buf| buf.len()).sum();
+            self.bytes += len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn buffer_writes_coalesces() {
+        const RECORDS: usize = 100;
+        let record = Record::builder().level(Level::Info).target("t").build();
+
+        // Other tests write records without a connection, so are not buffered.
+        BUFFER_CAPACITY.store(0, Ordering::Relaxed);
+        let mut unbuffered = Connection::new(CountingStream::default());
+        for _ in 0..RECORDS {
+            write_record(&mut unbuffered, &record, b"message").unwrap();
+        }
+        assert_eq!(unbuffered.stream.writes, RECORDS);
+
+        BUFFER_CAPACITY.store(64 * 1024, Ordering::Relaxed);
+        let mut buffered = Connection::new(CountingStream::default());
+        for _ in 0..RECORDS {
+            write_record(&mut buffered, &record, b"message").unwrap();
+        }
+        assert_eq!(buffered.stream.writes, 0);
+        buffered.flush().unwrap();
+        BUFFER_CAPACITY.store(0, Ordering::Relaxed);
+        assert_eq!(buffered.stream.writes, 1);
+        assert_eq!(buffered.stream.bytes, unbuffered.stream.bytes);
+        assert!(buffered.buffer.is_empty());
     }
 }
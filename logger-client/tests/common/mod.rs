@@ -0,0 +1,44 @@
+//! A stand-in for the server, receiving what the logger sends so it can be checked.
+
+use std::{io::Read, os::unix::net::UnixListener, thread::JoinHandle};
+
+use logger_wire::Header;
+
+/// Listens on a new socket named after `name`, returning its path and the thread receiving every
+/// byte sent on the first connection to it until the connection is closed.
+pub fn listen(name: &str) -> (String, JoinHandle<Vec<u8>>) {
+    let socket = std::env::temp_dir().join(format!("mp-logger-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket).unwrap();
+    let receiver = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).unwrap();
+        bytes
+    });
+    (socket.into_os_string().into_string().unwrap(), receiver)
+}
+
+/// Splits the bytes received on a connection into its frames, checking the handshake.
+pub fn frames(bytes: &[u8]) -> Vec<(Header, Vec<u8>)> {
+    let (handshake, mut bytes) = bytes.split_at(2);
+    logger_wire::check_handshake(handshake.try_into().unwrap()).unwrap();
+    let mut frames = Vec::new();
+    while !bytes.is_empty() {
+        let (header, rest) = bytes.split_at(Header::LEN);
+        let header = Header::decode(header).unwrap();
+        let (message, rest) = rest.split_at(usize::try_from(header.length).unwrap());
+        frames.push((header, message.to_vec()));
+        bytes = rest;
+    }
+    frames
+}
+
+/// Returns the text of the record in the log frame `header` holding `message`, which follows its
+/// target, module path and file.
+pub fn text(header: &Header, message: &[u8]) -> String {
+    let source = usize::from(header.target_length)
+        + usize::from(header.module_path_length)
+        + usize::from(header.file_length);
+    String::from_utf8(message[source..].to_vec()).unwrap()
+}
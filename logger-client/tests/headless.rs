@@ -0,0 +1,53 @@
+use std::{
+    path::PathBuf,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use log::LevelFilter;
+use logger_client::{Logger, SpawnStrategy};
+
+/// Builds the server, returning the directory holding its binary.
+///
+/// It is built into a target directory of its own, as the one of this test is locked while the
+/// tests run.
+fn build_server() -> PathBuf {
+    let target = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../target/headless-test");
+    let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| String::from("cargo")))
+        .args(["build", "--package", "logger-server", "--target-dir"])
+        .arg(&target)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    target.join("debug")
+}
+
+#[test]
+fn headless_server_prints_logs() {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let paths = std::iter::once(build_server()).chain(std::env::split_paths(&path));
+    std::env::set_var("PATH", std::env::join_paths(paths).unwrap());
+
+    let socket = std::env::temp_dir().join(format!("mp-logger-headless-{}", std::process::id()));
+    let socket = socket.into_os_string().into_string().unwrap();
+    let output = format!("{socket}.out");
+    let _ = std::fs::remove_file(&socket);
+    Logger::set_spawn_timeout(Duration::from_secs(30));
+    Logger::init_with_spawner(&socket, LevelFilter::Info, SpawnStrategy::Headless).unwrap();
+    log::info!("spawned headless");
+    Logger::shutdown();
+
+    // The server prints each log as it arrives.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut printed = String::new();
+    while !printed.contains("spawned headless") && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+        printed = std::fs::read_to_string(&output).unwrap_or_default();
+    }
+    // The server removes its socket on `SIGTERM`.
+    let _ = Command::new("pkill")
+        .args(["-TERM", "-f", "--", &socket])
+        .status();
+    assert!(printed.contains("spawned headless"), "{printed}");
+    let _ = std::fs::remove_file(&output);
+}
@@ -0,0 +1,35 @@
+use log::LevelFilter;
+use logger_client::Logger;
+use logger_wire::{KEY_VALUES_FRAME, LOG_FRAME};
+
+mod common;
+
+#[test]
+fn key_values_precede_their_record() {
+    let (socket, receiver) = common::listen("key-values");
+    Logger::init(&socket, LevelFilter::Info).unwrap();
+    log::info!(user = 42; "hello");
+    log::info!("goodbye");
+    Logger::shutdown();
+
+    let frames = common::frames(&receiver.join().unwrap());
+    let records = frames
+        .iter()
+        .filter(|(header, _)| matches!(header.kind, KEY_VALUES_FRAME | LOG_FRAME))
+        .collect::<Vec<_>>();
+    // Only the record with key-value pairs is preceded by them.
+    assert_eq!(records.len(), 3);
+    let (header, message) = records[0];
+    assert_eq!(header.kind, KEY_VALUES_FRAME);
+    assert_eq!(
+        logger_wire::decode_key_values(message).unwrap(),
+        [(String::from("user"), String::from("42"))]
+    );
+    let (header, message) = records[1];
+    assert_eq!(header.kind, LOG_FRAME);
+    assert_eq!(common::text(header, message), "hello");
+    let (header, message) = records[2];
+    assert_eq!(header.kind, LOG_FRAME);
+    assert_eq!(common::text(header, message), "goodbye");
+    std::fs::remove_file(socket).unwrap();
+}
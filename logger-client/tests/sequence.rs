@@ -0,0 +1,41 @@
+use log::LevelFilter;
+use logger_client::Logger;
+use logger_wire::LOG_FRAME;
+
+mod common;
+
+const THREADS: usize = 4;
+const RECORDS: usize = 1000;
+
+#[test]
+fn sequence_increases_across_threads() {
+    let (socket, receiver) = common::listen("sequence");
+    Logger::init(&socket, LevelFilter::Info).unwrap();
+    let threads = (0..THREADS)
+        .map(|thread| {
+            std::thread::spawn(move || {
+                for i in 0..RECORDS / THREADS {
+                    log::info!("thread {thread} record {i}");
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    Logger::shutdown();
+
+    let frames = common::frames(&receiver.join().unwrap());
+    // Every frame is numbered, including those naming the process and its threads, in the order
+    // they are sent.
+    assert!(frames
+        .windows(2)
+        .all(|pair| pair[0].0.sequence < pair[1].0.sequence));
+    let logs = frames
+        .iter()
+        .filter(|(header, _)| header.kind == LOG_FRAME)
+        .map(|(header, message)| common::text(header, message))
+        .collect::<Vec<_>>();
+    assert_eq!(logs.len(), RECORDS);
+    std::fs::remove_file(socket).unwrap();
+}
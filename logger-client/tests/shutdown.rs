@@ -0,0 +1,35 @@
+use log::LevelFilter;
+use logger_client::Logger;
+use logger_wire::LOG_FRAME;
+
+mod common;
+
+const RECORDS: usize = 1000;
+
+#[test]
+fn shutdown_sends_buffered_and_queued_records() {
+    let (socket, receiver) = common::listen("shutdown");
+    Logger::init(&socket, LevelFilter::Info).unwrap();
+    Logger::buffer_writes(64 * 1024);
+    Logger::send_in_background(RECORDS);
+    for i in 0..RECORDS {
+        log::info!("record {i}");
+    }
+    Logger::shutdown();
+
+    let bytes = receiver.join().unwrap();
+    let logs = common::frames(&bytes)
+        .into_iter()
+        .filter(|(header, _)| header.kind == LOG_FRAME)
+        .map(|(header, message)| common::text(&header, &message))
+        .collect::<Vec<_>>();
+    let expected = (0..RECORDS)
+        .map(|i| format!("record {i}"))
+        .collect::<Vec<_>>();
+    assert_eq!(logs, expected);
+    assert_eq!(Logger::dropped_records(), 0);
+
+    log::info!("after the shutdown");
+    assert_eq!(Logger::dropped_records(), 1);
+    std::fs::remove_file(socket).unwrap();
+}
@@ -0,0 +1,139 @@
+//! Rendering the ANSI colors and styles of messages, enabled with `--ansi`.
+//!
+//! Only SGR sequences (`ESC [ <PARAMS> m`) are rendered. Every other escape sequence, such as
+//! those moving the cursor or clearing the screen, is removed so it cannot affect the terminal,
+//! and the remaining control characters are escaped as they are without `--ansi`.
+
+use tui::style::{Color, Modifier, Style};
+
+/// The colors of the SGR parameters `30` to `37`.
+const BASIC: [Color; 8] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+];
+/// The colors of the SGR parameters `90` to `97`.
+const BRIGHT: [Color; 8] = [
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+/// Splits `message` into runs of text with the style given to them by its SGR sequences,
+/// removing every other escape sequence.
+pub fn parse(message: &str) -> Vec<(Style, String)> {
+    let mut runs = Vec::new();
+    let mut style = Style::default();
+    let mut text = String::new();
+    let mut chars = message.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            if c.is_control() {
+                text.extend(c.escape_debug());
+            } else {
+                text.push(c);
+            }
+            continue;
+        }
+        match chars.next() {
+            // A control sequence, parameters and intermediate bytes ended by a final byte.
+            Some('[') => {
+                let mut params = String::new();
+                let end = chars.by_ref().find(|c| {
+                    let end = ('\x40'..='\x7e').contains(c);
+                    if !end {
+                        params.push(*c);
+                    }
+                    end
+                });
+                if end == Some('m') {
+                    if !text.is_empty() {
+                        runs.push((style, std::mem::take(&mut text)));
+                    }
+                    style = apply(style, &params);
+                }
+            }
+            // An operating system command, ended by BEL or `ESC \`.
+            Some(']') => {
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    if c == '\x07' || (escaped && c == '\\') {
+                        break;
+                    }
+                    escaped = c == '\x1b';
+                }
+            }
+            // Any other escape sequence is a single character.
+            _ => {}
+        }
+    }
+    if !text.is_empty() {
+        runs.push((style, text));
+    }
+    runs
+}
+
+/// Returns the text of `message` without its escape sequences.
+pub fn strip(message: &str) -> String {
+    parse(message).into_iter().map(|(_, text)| text).collect()
+}
+
+/// Returns `style` with the SGR parameters `params` applied.
+fn apply(mut style: Style, params: &str) -> Style {
+    // An empty parameter, as in `ESC [ m`, is a reset.
+    let mut params = params
+        .split([';', ':'])
+        .map(|param| param.parse::<u16>().unwrap_or(0));
+    while let Some(param) = params.next() {
+        style = match param {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            6 => style.add_modifier(Modifier::RAPID_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            8 => style.add_modifier(Modifier::HIDDEN),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style.remove_modifier(Modifier::ITALIC),
+            24 => style.remove_modifier(Modifier::UNDERLINED),
+            25 => style.remove_modifier(Modifier::SLOW_BLINK | Modifier::RAPID_BLINK),
+            27 => style.remove_modifier(Modifier::REVERSED),
+            28 => style.remove_modifier(Modifier::HIDDEN),
+            29 => style.remove_modifier(Modifier::CROSSED_OUT),
+            30..=37 => style.fg(BASIC[usize::from(param - 30)]),
+            38 => extended(&mut params).map_or(style, |color| style.fg(color)),
+            39 => Style { fg: None, ..style },
+            40..=47 => style.bg(BASIC[usize::from(param - 40)]),
+            48 => extended(&mut params).map_or(style, |color| style.bg(color)),
+            49 => Style { bg: None, ..style },
+            90..=97 => style.fg(BRIGHT[usize::from(param - 90)]),
+            100..=107 => style.bg(BRIGHT[usize::from(param - 100)]),
+            _ => style,
+        };
+    }
+    style
+}
+
+/// Reads the color following the SGR parameter `38` or `48`, either `5;<INDEX>` or
+/// `2;<R>;<G>;<B>`.
+fn extended(params: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    let mut next = || params.next().and_then(|param| u8::try_from(param).ok());
+    match next()? {
+        5 => Some(Color::Indexed(next()?)),
+        2 => Some(Color::Rgb(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
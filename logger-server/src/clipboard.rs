@@ -0,0 +1,42 @@
+//! Copying text out of the interface, to the system clipboard or, where there is none such as over
+//! SSH or without a display server, to a file.
+
+use std::{path::PathBuf, sync::Mutex, time::SystemTime};
+
+use arboard::Clipboard;
+
+/// The clipboard, kept open since on some platforms copied text is only available while it is.
+static CLIPBOARD: Mutex<Option<Clipboard>> = Mutex::new(None);
+
+/// Where copied text went.
+pub enum Copied {
+    Clipboard,
+    /// The file written in the temporary directory when there is no clipboard.
+    File(PathBuf),
+}
+
+/// Copies `text` to the system clipboard, or writes it to a file in the temporary directory when
+/// there is no clipboard.
+///
+/// # Errors
+///
+/// When there is no clipboard and failing to write the file.
+pub fn copy(text: &str) -> std::io::Result<Copied> {
+    let mut clipboard = CLIPBOARD.lock().unwrap();
+    if clipboard.is_none() {
+        *clipboard = Clipboard::new().ok();
+    }
+    if let Some(clipboard) = clipboard.as_mut() {
+        if clipboard.set_text(text).is_ok() {
+            return Ok(Copied::Clipboard);
+        }
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let path = std::env::temp_dir().join(format!("mp-logger-copy-{timestamp}.txt"));
+    std::fs::write(&path, text)?;
+    Ok(Copied::File(path))
+}
@@ -0,0 +1,580 @@
+//! The command palette, opened with `:`, which runs actions by name.
+
+use std::ops::RangeInclusive;
+
+use clap::ValueEnum;
+
+use crate::{
+    export::{self, ExportFormat},
+    format_duration,
+    session::Session,
+    App, CountScope, View,
+};
+
+/// The result of running a command, holding the message to show in the status bar.
+type Outcome = Result<Option<String>, String>;
+
+/// A command which can be run from the command palette.
+pub struct Command {
+    pub name: &'static str,
+    /// The arguments taken, shown alongside the name when completing.
+    pub usage: &'static str,
+    pub help: &'static str,
+    /// The number of arguments accepted.
+    args: RangeInclusive<usize>,
+    run: fn(&mut App, &[&str]) -> Outcome,
+}
+
+/// Every command, in the order they are listed.
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "alias",
+        usage: "[NAME]",
+        help: "Names the selected process, removing its name when none is given",
+        args: 0..=usize::MAX,
+        run: alias,
+    },
+    Command {
+        name: "bucket",
+        usage: "<MILLIS>",
+        help: "Sets the interval each bar of the histogram covers",
+        args: 1..=1,
+        run: bucket,
+    },
+    Command {
+        name: "by-process",
+        usage: "",
+        help: "Toggles showing the logs of all threads of the selected process together",
+        args: 0..=0,
+        run: |app, _| {
+            app.by_process = !app.by_process;
+            app.scroll_to_top();
+            Ok(None)
+        },
+    },
+    Command {
+        name: "compare",
+        usage: "",
+        help: "Marks or unmarks the selected thread for comparison",
+        args: 0..=0,
+        run: |app, _| {
+            app.toggle_compare_thread();
+            Ok(None)
+        },
+    },
+    Command {
+        name: "compare-clear",
+        usage: "",
+        help: "Unmarks all threads marked for comparison",
+        args: 0..=0,
+        run: |app, _| {
+            app.compared_threads.clear();
+            app.scroll_to_top();
+            Ok(None)
+        },
+    },
+    Command {
+        name: "copy",
+        usage: "",
+        help: "Copies the top log to the clipboard, or to a file when there is no clipboard",
+        args: 0..=0,
+        run: |app, _| Ok(Some(app.copy_top_log())),
+    },
+    Command {
+        name: "correlate",
+        usage: "[ID]",
+        help: "Shows the logs with the correlation id ID from every process merged by time, or \
+               those with the id of the top log when none is given",
+        args: 0..=1,
+        run: correlate,
+    },
+    Command {
+        name: "correlate-clear",
+        usage: "",
+        help: "Shows the logs of the selected thread again after `correlate`",
+        args: 0..=0,
+        run: |app, _| {
+            app.correlation = None;
+            app.scroll_to_top();
+            Ok(None)
+        },
+    },
+    Command {
+        name: "counts",
+        usage: "[global|thread]",
+        help: "Shows the number of logs at each level of every process or of the selected thread \
+               above the log pane, or hides them when none is given",
+        args: 0..=1,
+        run: |app, args| {
+            app.counts = match args.first().copied() {
+                None => None,
+                Some("global") => Some(CountScope::Global),
+                Some("thread") => Some(CountScope::Thread),
+                Some(scope) => return Err(format!("Unknown scope `{scope}`")),
+            };
+            Ok(None)
+        },
+    },
+    Command {
+        name: "dedup",
+        usage: "",
+        help: "Toggles folding logs with the same level and message as the previous log of their \
+               thread into it",
+        args: 0..=0,
+        run: |app, _| {
+            app.dedup = !app.dedup;
+            Ok(None)
+        },
+    },
+    Command {
+        name: "detail",
+        usage: "",
+        help: "Shows the process, thread and log panes",
+        args: 0..=0,
+        run: |app, _| {
+            app.view = View::Detail;
+            Ok(None)
+        },
+    },
+    Command {
+        name: "expand",
+        usage: "",
+        help: "Lists or stops listing the threads of the selected process beneath it in the tree",
+        args: 0..=0,
+        run: |app, _| {
+            app.toggle_expanded();
+            Ok(None)
+        },
+    },
+    Command {
+        name: "export",
+        usage: "<json|csv|errors|warnings> [selected] [session]",
+        help: "Writes every log, or only errors or warnings and errors in a readable format, to a \
+               file in the working directory, only from the selected process with `selected` and \
+               only from the session the log pane is limited to with `session`",
+        args: 1..=3,
+        run: export,
+    },
+    Command {
+        name: "filter",
+        usage: "[TEXT] | level <LEVEL>",
+        help: "Limits the log pane to logs whose messages contain TEXT ignoring case, or shows \
+               every log again when none is given, or with `level` hides the logs more verbose \
+               than LEVEL like `+` and `-`",
+        args: 0..=2,
+        run: filter,
+    },
+    Command {
+        name: "follow",
+        usage: "",
+        help: "Toggles keeping the latest log in view as logs arrive",
+        args: 0..=0,
+        run: |app, _| {
+            app.follow = !app.follow;
+            Ok(None)
+        },
+    },
+    Command {
+        name: "goto",
+        usage: "<INDEX>",
+        help: "Scrolls the log pane to the log with the given hexadecimal index",
+        args: 1..=1,
+        run: goto,
+    },
+    Command {
+        name: "group",
+        usage: "",
+        help: "Toggles grouping logs by level",
+        args: 0..=0,
+        run: |app, _| {
+            app.group_by_level = !app.group_by_level;
+            Ok(None)
+        },
+    },
+    Command {
+        name: "histogram",
+        usage: "",
+        help: "Toggles the histogram of log volume",
+        args: 0..=0,
+        run: |app, _| {
+            app.show_histogram = !app.show_histogram;
+            Ok(None)
+        },
+    },
+    Command {
+        name: "key-values",
+        usage: "",
+        help: "Toggles listing the key-value pairs of logs one per line beneath their messages",
+        args: 0..=0,
+        run: |app, _| {
+            app.expand_key_values = !app.expand_key_values;
+            Ok(None)
+        },
+    },
+    Command {
+        name: "marker",
+        usage: "<LABEL>",
+        help: "Adds a marker labelled LABEL at the current time",
+        args: 1..=usize::MAX,
+        run: |app, args| {
+            app.add_marker(args.join(" "));
+            Ok(None)
+        },
+    },
+    Command {
+        name: "overview",
+        usage: "",
+        help: "Shows the process overview",
+        args: 0..=0,
+        run: |app, _| {
+            app.view = View::Overview;
+            Ok(None)
+        },
+    },
+    Command {
+        name: "quit",
+        usage: "",
+        help: "Exits the server",
+        args: 0..=0,
+        run: |app, _| {
+            app.quit = true;
+            Ok(None)
+        },
+    },
+    Command {
+        name: "raw",
+        usage: "",
+        help: "Toggles showing the raw bytes of the top log",
+        args: 0..=0,
+        run: |app, _| {
+            app.show_raw = !app.show_raw;
+            Ok(None)
+        },
+    },
+    Command {
+        name: "search",
+        usage: "[TEXT]",
+        help: "Lists the logs of every process and thread whose messages contain TEXT ignoring \
+               case, or shows the log pane again when none is given",
+        args: 0..=usize::MAX,
+        run: |app, args| {
+            app.set_search(&args.join(" "));
+            Ok(None)
+        },
+    },
+    Command {
+        name: "session",
+        usage: "[NUMBER]",
+        help: "Limits the log pane to the logs of the session with the given number, or shows \
+               every log again when none is given",
+        args: 0..=1,
+        run: session,
+    },
+    Command {
+        name: "session-start",
+        usage: "[NAME]",
+        help: "Starts a session at the current time, stopping the running session",
+        args: 0..=usize::MAX,
+        run: session_start,
+    },
+    Command {
+        name: "session-stop",
+        usage: "",
+        help: "Stops the running session at the current time",
+        args: 0..=0,
+        run: |app, _| {
+            let session = app
+                .sessions
+                .last_mut()
+                .filter(|session| session.end.is_none())
+                .ok_or("No session is running")?;
+            session.stop();
+            let label = format!("Stopped session {}", session.name);
+            app.add_marker(label);
+            Ok(None)
+        },
+    },
+    Command {
+        name: "sessions",
+        usage: "",
+        help: "Lists every session with its number",
+        args: 0..=0,
+        run: sessions,
+    },
+    Command {
+        name: "target",
+        usage: "[TARGET]",
+        help: "Limits the log pane to logs with the target TARGET or a target nested in it, or \
+               the target of the top log when none is given",
+        args: 0..=1,
+        run: target,
+    },
+    Command {
+        name: "target-clear",
+        usage: "",
+        help: "Shows the logs of every target again after `target`",
+        args: 0..=0,
+        run: |app, _| {
+            app.target_filter = None;
+            app.scroll_to_top();
+            Ok(None)
+        },
+    },
+    Command {
+        name: "timeline",
+        usage: "",
+        help: "Toggles showing the logs of every thread of every process merged by time",
+        args: 0..=0,
+        run: |app, _| {
+            app.timeline = !app.timeline;
+            app.scroll_to_top();
+            Ok(None)
+        },
+    },
+    Command {
+        name: "tree",
+        usage: "",
+        help: "Toggles listing threads beneath their processes in a single tree",
+        args: 0..=0,
+        run: |app, _| {
+            app.tree = !app.tree;
+            Ok(None)
+        },
+    },
+];
+
+/// Returns the commands matching the partially entered `line`.
+///
+/// While the name is being entered these are the commands starting with it, afterwards it is the
+/// command with that name.
+pub fn completions(line: &str) -> Vec<&'static Command> {
+    let line = line.trim_start();
+    match line.split_once(char::is_whitespace) {
+        Some((name, _)) => COMMANDS
+            .iter()
+            .filter(|command| command.name == name)
+            .collect(),
+        None => COMMANDS
+            .iter()
+            .filter(|command| command.name.starts_with(line))
+            .collect(),
+    }
+}
+
+/// Extends the name in `line` as far as the names of all matching commands allow.
+pub fn complete(line: &mut String) {
+    if line.contains(char::is_whitespace) {
+        return;
+    }
+    match completions(line).as_slice() {
+        [] => {}
+        [command] => *line = format!("{} ", command.name),
+        [first, rest @ ..] => {
+            let shared = rest.iter().fold(first.name.len(), |len, command| {
+                first
+                    .name
+                    .bytes()
+                    .zip(command.name.bytes())
+                    .take(len)
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            });
+            *line = first.name[..shared].to_string();
+        }
+    }
+}
+
+/// Runs the command `line` on `app`.
+///
+/// # Errors
+///
+/// When the command is unknown, given the wrong number of arguments or fails.
+pub fn run(app: &mut App, line: &str) -> Outcome {
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        return Ok(None);
+    };
+    let args = words.collect::<Vec<_>>();
+
+    let command = COMMANDS
+        .iter()
+        .find(|command| command.name == name)
+        .ok_or_else(|| format!("Unknown command `{name}`, press tab for a list of commands"))?;
+    if !command.args.contains(&args.len()) {
+        return Err(format!("Usage: {} {}", command.name, command.usage));
+    }
+    (command.run)(app, &args)
+}
+
+fn alias(app: &mut App, args: &[&str]) -> Outcome {
+    app.clamp_selection();
+    let process = app.process.selected().ok_or("No process selected")?;
+    let pid = app.processes[process].id;
+    if args.is_empty() {
+        app.aliases.remove(&pid);
+    } else {
+        app.aliases.insert(pid, args.join(" "));
+    }
+    Ok(None)
+}
+
+fn bucket(app: &mut App, args: &[&str]) -> Outcome {
+    let millis = args[0]
+        .parse::<u64>()
+        .ok()
+        .filter(|&millis| millis > 0)
+        .ok_or_else(|| format!("Invalid interval `{}`", args[0]))?;
+    app.histogram_bucket = std::time::Duration::from_millis(millis);
+    Ok(None)
+}
+
+fn correlate(app: &mut App, args: &[&str]) -> Outcome {
+    let correlation = match args.first() {
+        Some(id) => (*id).to_string(),
+        None => {
+            app.clamp_selection();
+            app.visible_logs()
+                .first()
+                .and_then(|(_, _, log)| log.correlation.clone())
+                .ok_or("The top log has no correlation id")?
+        }
+    };
+    app.correlation = Some(correlation);
+    app.scroll_to_top();
+    Ok(None)
+}
+
+fn export(app: &mut App, args: &[&str]) -> Outcome {
+    let mut pid = None;
+    let mut session = None;
+    for scope in &args[1..] {
+        match *scope {
+            "selected" => {
+                app.clamp_selection();
+                let process = app.process.selected().ok_or("No process selected")?;
+                pid = Some(app.processes[process].id);
+            }
+            "session" => {
+                session = Some(
+                    app.session
+                        .ok_or("The log pane is not limited to a session")?,
+                );
+            }
+            scope => return Err(format!("Unknown scope `{scope}`")),
+        }
+    }
+    let session = session.map(|session| &app.sessions[session]);
+    let path = match args[0] {
+        "errors" => export::report(app, log::Level::Error, pid, session),
+        "warnings" => export::report(app, log::Level::Warn, pid, session),
+        format => {
+            let format = ExportFormat::from_str(format, true)
+                .map_err(|_| format!("Unknown format `{format}`"))?;
+            if pid.is_some() || session.is_some() {
+                return Err(String::from(
+                    "Only reports can be limited to the selected process or a session",
+                ));
+            }
+            export::export(app, format)
+        }
+    }
+    .map_err(|err| format!("Failed to export: {err}"))?;
+    Ok(Some(format!("Exported to {}", path.display())))
+}
+
+fn session(app: &mut App, args: &[&str]) -> Outcome {
+    app.session = match args.first() {
+        Some(number) => {
+            let index = number
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| number.checked_sub(1))
+                .filter(|&index| index < app.sessions.len())
+                .ok_or_else(|| format!("No session numbered `{number}`"))?;
+            Some(index)
+        }
+        None => None,
+    };
+    app.scroll_to_top();
+    Ok(None)
+}
+
+fn session_start(app: &mut App, args: &[&str]) -> Outcome {
+    if let Some(running) = app
+        .sessions
+        .last_mut()
+        .filter(|session| session.end.is_none())
+    {
+        running.stop();
+    }
+    let name = if args.is_empty() {
+        (app.sessions.len() + 1).to_string()
+    } else {
+        args.join(" ")
+    };
+    app.add_marker(format!("Started session {name}"));
+    app.sessions.push(Session::start(name));
+    Ok(Some(format!("Started session {}", app.sessions.len())))
+}
+
+fn sessions(app: &mut App, _: &[&str]) -> Outcome {
+    if app.sessions.is_empty() {
+        return Err(String::from("No sessions, start one with `session-start`"));
+    }
+    let list = app
+        .sessions
+        .iter()
+        .enumerate()
+        .map(|(i, session)| {
+            let length = session.end.map_or_else(
+                || String::from("running"),
+                |end| format_duration(end.saturating_sub(session.start)),
+            );
+            format!("{}: {} ({length})", i + 1, session.name)
+        })
+        .collect::<Vec<_>>();
+    Ok(Some(list.join(", ")))
+}
+
+fn filter(app: &mut App, args: &[&str]) -> Outcome {
+    match args {
+        ["level", level] => {
+            app.display_level = level
+                .parse()
+                .map_err(|_| format!("Unknown level `{level}`"))?;
+            app.scroll_to_top();
+        }
+        [_, _] => return Err(String::from("Usage: filter [TEXT] | level <LEVEL>")),
+        _ => app.set_filter(args.first().copied().unwrap_or_default()),
+    }
+    Ok(None)
+}
+
+fn goto(app: &mut App, args: &[&str]) -> Outcome {
+    let index = usize::from_str_radix(args[0].trim_start_matches("0x"), 16)
+        .map_err(|_| format!("Invalid index `{}`", args[0]))?;
+    app.clamp_selection();
+    let (first, last) = app.log_bounds().ok_or("No logs selected")?;
+    if !(first..=last).contains(&index) {
+        return Err(format!("No log with index {index:x}"));
+    }
+    app.log = index;
+    app.follow = false;
+    Ok(None)
+}
+
+fn target(app: &mut App, args: &[&str]) -> Outcome {
+    let target = match args.first() {
+        Some(target) => (*target).to_string(),
+        None => {
+            app.clamp_selection();
+            app.visible_logs()
+                .first()
+                .and_then(|(_, _, log)| log.target.clone())
+                .ok_or("The top log has no target")?
+        }
+    };
+    app.target_filter = Some(target);
+    app.scroll_to_top();
+    Ok(None)
+}
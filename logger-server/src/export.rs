@@ -0,0 +1,220 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use clap::ValueEnum;
+use nix::{sys::pthread::Pthread, unistd::Pid};
+use serde::{Serialize, Serializer};
+
+use crate::{session::Session, App, Log, Process};
+
+/// The file format used when exporting the collected logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+/// A process as it is exported, along with its alias and name.
+#[derive(Serialize)]
+struct ExportProcess<'a> {
+    alias: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<&'a [(String, String)]>,
+    #[serde(flatten)]
+    process: &'a Process,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+pub fn serialize_pid<S: Serializer>(pid: &Pid, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i32(pid.as_raw())
+}
+
+/// Exports all processes, threads and logs currently held by `app` to a timestamped file in the
+/// working directory, returning the path written.
+///
+/// # Errors
+///
+/// When failing to create or write to the file.
+pub fn export(app: &App, format: ExportFormat) -> std::io::Result<PathBuf> {
+    let path = timestamped_path("mp-logger", format.extension());
+
+    let mut writer = BufWriter::new(File::create(&path)?);
+    match format {
+        ExportFormat::Json => {
+            let processes = app
+                .processes
+                .iter()
+                .map(|process| ExportProcess {
+                    alias: app.aliases.get(&process.id).map(String::as_str),
+                    name: app.process_names.get(&process.id).map(String::as_str),
+                    context: app.process_contexts.get(&process.id).map(Vec::as_slice),
+                    process,
+                })
+                .collect::<Vec<_>>();
+            serde_json::to_writer_pretty(&mut writer, &processes)?;
+        }
+        ExportFormat::Csv => write_csv(&mut writer, app)?,
+    }
+    writer.flush()?;
+
+    Ok(path)
+}
+
+/// Writes the logs at or above `level`, of only the process `pid` and within only `session` if
+/// given, to a timestamped text file in the working directory, returning the path written.
+///
+/// Each log is written on one line in the order they were logged, ready to be shared in a bug
+/// report.
+///
+/// # Errors
+///
+/// When failing to create or write to the file.
+pub fn report(
+    app: &App,
+    level: log::Level,
+    pid: Option<Pid>,
+    session: Option<&Session>,
+) -> std::io::Result<PathBuf> {
+    let path = timestamped_path("mp-logger-report", "txt");
+
+    let mut logs = app
+        .processes
+        .iter()
+        .filter(|process| pid.is_none() || pid == Some(process.id))
+        .flat_map(|process| {
+            process.threads.iter().flat_map(move |thread| {
+                thread
+                    .log
+                    .iter()
+                    .filter(|log| log.level <= level)
+                    .filter(|log| session.is_none_or(|session| session.contains(log.time)))
+                    .map(move |log| (process, thread, log))
+            })
+        })
+        .collect::<Vec<_>>();
+    crate::sort_logs(&mut logs, |&(process, _, log)| (process.id, log));
+
+    let mut writer = BufWriter::new(File::create(&path)?);
+    for (process, thread, log) in logs {
+        write_line(&mut writer, app.process_full_label(process), thread.id, log)?;
+    }
+    writer.flush()?;
+
+    Ok(path)
+}
+
+/// The widths the level, process and thread of logs are padded to by [`write_aligned_line`].
+const LEVEL_WIDTH: usize = 5;
+const PROCESS_WIDTH: usize = 7;
+const THREAD_WIDTH: usize = 12;
+
+/// Writes `log` from the thread `thread` of `process` as a single readable line, with line breaks
+/// in its message escaped so every log can be found with `grep`.
+///
+/// # Errors
+///
+/// When failing to write to `writer`.
+pub fn write_line(
+    writer: &mut impl Write,
+    process: impl Display,
+    thread: Pthread,
+    log: &Log,
+) -> std::io::Result<()> {
+    write!(
+        writer,
+        "{}.{:09} {:<5} {process} {thread:x} ",
+        log.time.as_secs(),
+        log.time.subsec_nanos(),
+        log.severity().name(),
+    )?;
+    write_message(writer, log)
+}
+
+/// Writes `log` like [`write_line`], with its level, process and thread padded to fixed widths so
+/// the messages of consecutive lines line up, truncating them with `…` when wider.
+///
+/// # Errors
+///
+/// When failing to write to `writer`.
+pub fn write_aligned_line(
+    writer: &mut impl Write,
+    process: impl Display,
+    thread: Pthread,
+    log: &Log,
+) -> std::io::Result<()> {
+    let column = |text: &str, width: usize| format!("{:<width$}", crate::truncate(text, width));
+    write!(
+        writer,
+        "{}.{:09} {} {} {} ",
+        log.time.as_secs(),
+        log.time.subsec_nanos(),
+        column(log.severity().name(), LEVEL_WIDTH),
+        column(&process.to_string(), PROCESS_WIDTH),
+        column(&format!("{thread:x}"), THREAD_WIDTH),
+    )?;
+    write_message(writer, log)
+}
+
+/// Writes the message of `log`, with line breaks escaped, and how many logs it stands for, ending
+/// the line.
+fn write_message(writer: &mut impl Write, log: &Log) -> std::io::Result<()> {
+    write!(
+        writer,
+        "{}",
+        log.message.replace('\\', "\\\\").replace('\n', "\\n")
+    )?;
+    if log.count > 1 {
+        write!(writer, " (x{})", log.count)?;
+    }
+    writeln!(writer)
+}
+
+/// Returns a path in the working directory named after the current time.
+fn timestamped_path(prefix: &str, extension: &str) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    PathBuf::from(format!("{prefix}-{timestamp}.{extension}"))
+}
+
+fn write_csv(writer: &mut impl Write, app: &App) -> std::io::Result<()> {
+    writeln!(writer, "pid,alias,thread,secs,nanos,level,message")?;
+    for process in &app.processes {
+        let alias = app
+            .aliases
+            .get(&process.id)
+            .map_or_else(String::new, |alias| alias.replace('"', "\"\""));
+        for thread in &process.threads {
+            for log in &thread.log {
+                writeln!(
+                    writer,
+                    "{},\"{alias}\",{},{},{},{},\"{}\"",
+                    process.id,
+                    thread.id,
+                    log.time.as_secs(),
+                    log.time.subsec_nanos(),
+                    log.severity().name(),
+                    log.message.replace('"', "\"\"")
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+//! Detection of clients stuck restarting, which connect, log a little and exit over and over.
+//!
+//! Each restart of a client has a new process id but is usually spawned by the same parent, so
+//! processes are grouped into lineages by their parent. A lineage is flapping while `threshold` of
+//! its processes have connected within `window`. Once a lineage has flapped only its newest
+//! process is listed, rather than one entry for every restart.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use nix::unistd::Pid;
+
+/// The process which adopts orphans, whose children are unrelated.
+const INIT: Pid = Pid::from_raw(1);
+
+pub struct Detector {
+    window: Duration,
+    threshold: usize,
+    lineages: HashMap<Pid, Lineage>,
+}
+
+#[derive(Default)]
+struct Lineage {
+    /// When processes of the lineage connected within the last `window`.
+    connects: VecDeque<Instant>,
+    /// Whether the lineage has ever been flapping.
+    flapped: bool,
+}
+
+impl Detector {
+    pub fn new(window: Duration, threshold: usize) -> Self {
+        Self {
+            window,
+            threshold,
+            lineages: HashMap::new(),
+        }
+    }
+
+    /// Records a process of `lineage` connecting.
+    pub fn connect(&mut self, lineage: Pid) {
+        let now = Instant::now();
+        let entry = self.lineages.entry(lineage).or_default();
+        entry.connects.push_back(now);
+        while entry
+            .connects
+            .front()
+            .is_some_and(|&connect| now.duration_since(connect) > self.window)
+        {
+            entry.connects.pop_front();
+        }
+        entry.flapped |= entry.connects.len() >= self.threshold;
+    }
+
+    /// Returns whether `lineage` has ever been flapping.
+    pub fn has_flapped(&self, lineage: Pid) -> bool {
+        self.lineages
+            .get(&lineage)
+            .is_some_and(|lineage| lineage.flapped)
+    }
+
+    /// Returns the lineages currently flapping along with how many of their processes connected
+    /// within the window.
+    pub fn flapping(&self) -> Vec<(Pid, usize)> {
+        self.lineages
+            .iter()
+            .filter_map(|(&pid, lineage)| {
+                let connects = lineage
+                    .connects
+                    .iter()
+                    .filter(|connect| connect.elapsed() <= self.window)
+                    .count();
+                (connects >= self.threshold).then_some((pid, connects))
+            })
+            .collect()
+    }
+}
+
+/// Returns the lineage of the process `pid`, its parent, or the process itself when its parent
+/// cannot be read or is `init`.
+pub fn lineage(pid: Pid) -> Pid {
+    parent(pid).filter(|&parent| parent != INIT).unwrap_or(pid)
+}
+
+/// Reads the parent of the process `pid` from `/proc`, which may be unavailable in sandboxes or
+/// once the process has exited.
+fn parent(pid: Pid) -> Option<Pid> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The name of the process, in parentheses, may itself contain spaces or parentheses.
+    let (_, fields) = stat.rsplit_once(')')?;
+    let parent = fields.split_whitespace().nth(1)?.parse().ok()?;
+    Some(Pid::from_raw(parent))
+}
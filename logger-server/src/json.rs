@@ -0,0 +1,172 @@
+//! The JSON wire format, allowing clients written in any language to send logs.
+//!
+//! Each log is a single line holding a JSON object:
+//!
+//! - `secs` (integer): Whole seconds since the Unix epoch.
+//! - `nanos` (integer, optional): Nanoseconds past `secs`, defaults to `0`.
+//! - `sequence` (integer, optional): The position of the log among those sent by its process,
+//!   ordering logs with the same time.
+//! - `pid` (integer): The id of the sending process.
+//! - `thread` (integer, optional): The id of the sending thread, defaults to `0`.
+//! - `thread_name` (string, optional): The name of the sending thread.
+//! - `process_name` (string, optional): The name of the sending process.
+//! - `level` (string or integer): One of `ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`, in any case,
+//!   or `1` to `5` respectively. Other severities are clamped as described in [`crate::level`].
+//! - `message` (string): The log message.
+//! - `correlation` (string, optional): An id to follow a request across processes and threads.
+//! - `target` (string, optional): The target of the log, such as the name of a subsystem.
+//! - `module_path` (string, optional): The module the log was made in.
+//! - `file` (string, optional): The source file the log was made in.
+//! - `line` (integer, optional): The line of `file` the log was made on.
+//! - `key_values` (object, optional): Structured key-value pairs, values other than strings are
+//!   shown as JSON.
+//!
+//! Lines which fail to parse, lines whose time is too far in the future to represent, and lines
+//! longer than `--max-frame-length`, are skipped.
+
+use std::{
+    collections::HashSet,
+    io::{BufRead, BufReader, Read},
+    sync::{Arc, RwLock},
+};
+
+use nix::{sys::pthread::Pthread, unistd::Pid};
+use serde::Deserialize;
+
+use crate::{level::Severity, log_time, App, Log, RawFrame};
+
+#[derive(Debug, Deserialize)]
+struct JsonLog {
+    secs: u64,
+    #[serde(default)]
+    nanos: u32,
+    sequence: Option<u64>,
+    pid: i32,
+    #[serde(default)]
+    thread: Pthread,
+    thread_name: Option<String>,
+    process_name: Option<String>,
+    level: Severity,
+    message: String,
+    correlation: Option<String>,
+    target: Option<String>,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    #[serde(default)]
+    key_values: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Receives logs from `stream`, the connection `id`, `local` when it was accepted on a Unix socket,
+/// see [`App::connect`].
+pub fn handle_stream(stream: impl Read, local: bool, id: usize, app: Arc<RwLock<App>>) {
+    // The processes which have logged over this connection.
+    let mut pids = HashSet::new();
+
+    let max_line_length = app.read().unwrap().max_frame_length;
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        // A misbehaving client could otherwise have the server buffer any amount, reading stops
+        // one byte past the limit and the rest of the line is skipped.
+        match reader
+            .by_ref()
+            .take(max_line_length.saturating_add(1))
+            .read_until(b'\n', &mut line)
+        {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        if !line.ends_with(b"\n") && u64::try_from(line.len()).unwrap_or(u64::MAX) > max_line_length
+        {
+            app.write().unwrap().system_log(
+                log::Level::Warn,
+                format!("Skipped a line on connection {id:08x}, longer than --max-frame-length"),
+            );
+            if reader.skip_until(b'\n').is_err() {
+                break;
+            }
+            continue;
+        }
+        let Ok(line) = String::from_utf8(std::mem::take(&mut line)) else {
+            continue;
+        };
+        let Ok(json) = serde_json::from_str::<JsonLog>(&line) else {
+            continue;
+        };
+        let Some(time) = log_time(json.secs, json.nanos) else {
+            continue;
+        };
+
+        let pid = Pid::from_raw(json.pid);
+        let mut app = app.write().unwrap();
+        if let Some(name) = json.thread_name.filter(|name| !name.is_empty()) {
+            app.thread_names.insert((pid, json.thread), name);
+        }
+        if let Some(name) = json.process_name.filter(|name| !name.is_empty()) {
+            app.process_names.insert(pid, name);
+        }
+        let Some(message) = app.nul_policy.apply(json.message) else {
+            continue;
+        };
+        let log = Log {
+            time,
+            sequence: json.sequence,
+            count: 1,
+            level: json.level.level,
+            fatal: json.level.fatal,
+            message,
+            correlation: json.correlation.filter(|id| !id.is_empty()),
+            target: json.target.filter(|target| !target.is_empty()),
+            module_path: json
+                .module_path
+                .filter(|module_path| !module_path.is_empty()),
+            file: json.file.filter(|file| !file.is_empty()),
+            line: json.line,
+            key_values: json
+                .key_values
+                .into_iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(value) => (key, value),
+                    value => (key, value.to_string()),
+                })
+                .collect(),
+            raw: app.keep_raw.then(|| RawFrame {
+                header: Vec::new(),
+                payload: line.into_bytes(),
+            }),
+        };
+        if app.add_log(pid, json.thread, log) && pids.insert(pid) {
+            app.connect(pid, local);
+        }
+    }
+
+    app.write().unwrap().disconnect(&pids);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn time_out_of_range() {
+        let app = Arc::new(RwLock::new(App::new()));
+        let lines = concat!(
+            r#"{"secs": 18446744073709551615, "nanos": 1000000000, "pid": 1, "level": "INFO", "#,
+            r#""message": "overflow"}"#,
+            "\n",
+            r#"{"secs": 1, "nanos": 1500000000, "pid": 1, "level": "INFO", "message": "carried"}"#,
+            "\n",
+        );
+        handle_stream(lines.as_bytes(), true, 0, app.clone());
+
+        let app = app.read().unwrap();
+        let logs = &app.processes[app.process_id_map[&Pid::from_raw(1)]].threads[0].log;
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].message, "carried");
+        assert_eq!(logs[0].time, Duration::from_millis(2500));
+    }
+}
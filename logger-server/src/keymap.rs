@@ -0,0 +1,89 @@
+//! The keys navigating the process, thread and log panes, chosen with `--keys`.
+//!
+//! Navigation keys are looked up before every other binding, so a keymap reusing a key, such as
+//! `k` in [`Keymap::Vim`], takes it from the binding it has in [`Keymap::Wasd`]. Every action
+//! losing its key remains available from the command palette. `q`, `Home`, `End`, `PageUp` and
+//! `PageDown` are bound in every keymap.
+
+use clap::ValueEnum;
+use crossterm::event::KeyCode;
+
+/// A set of navigation keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Keymap {
+    /// `w`/`s` for processes, `e`/`d` for threads and `r`/`f`, `t`/`g` and `y`/`h` for logs.
+    Wasd,
+    /// The arrow keys for threads and logs and `Tab` for processes.
+    Arrows,
+    /// `h`/`l` for threads, `j`/`k` for logs, `H`/`L` for processes and `g`/`G` for the ends.
+    Vim,
+}
+
+/// What a navigation key does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    PreviousProcess,
+    NextProcess,
+    PreviousThread,
+    NextThread,
+    /// Scrolls the log pane up by this many logs.
+    PreviousLog(usize),
+    /// Scrolls the log pane down by this many logs.
+    NextLog(usize),
+    /// Scrolls the log pane up by the number of logs it shows.
+    PreviousPage,
+    /// Scrolls the log pane down by the number of logs it shows.
+    NextPage,
+    /// Scrolls the log pane to its first log.
+    Top,
+    /// Scrolls the log pane to its last log.
+    Bottom,
+}
+
+impl Keymap {
+    /// Returns the action of `code`, or `None` when it is not a navigation key.
+    pub fn action(self, code: KeyCode) -> Option<Action> {
+        let action = match (self, code) {
+            (_, KeyCode::Char('q')) => Action::Quit,
+            (_, KeyCode::Home) => Action::Top,
+            (_, KeyCode::End) => Action::Bottom,
+            (_, KeyCode::PageUp) => Action::PreviousPage,
+            (_, KeyCode::PageDown) => Action::NextPage,
+
+            (Self::Wasd, KeyCode::Char('w')) => Action::PreviousProcess,
+            (Self::Wasd, KeyCode::Char('s')) => Action::NextProcess,
+            (Self::Wasd, KeyCode::Char('e')) => Action::PreviousThread,
+            (Self::Wasd, KeyCode::Char('d')) => Action::NextThread,
+            (Self::Wasd, KeyCode::Char('r')) => Action::PreviousLog(1),
+            (Self::Wasd, KeyCode::Char('f')) => Action::NextLog(1),
+            (Self::Wasd, KeyCode::Char('t')) => Action::PreviousLog(2),
+            (Self::Wasd, KeyCode::Char('g')) => Action::NextLog(2),
+            (Self::Wasd, KeyCode::Char('y')) => Action::PreviousLog(4),
+            (Self::Wasd, KeyCode::Char('h')) => Action::NextLog(4),
+            (Self::Wasd, KeyCode::Char('u')) => Action::Top,
+            (Self::Wasd, KeyCode::Char('j')) => Action::Bottom,
+
+            (Self::Arrows, KeyCode::BackTab) => Action::PreviousProcess,
+            (Self::Arrows, KeyCode::Tab) => Action::NextProcess,
+            (Self::Arrows, KeyCode::Left) => Action::PreviousThread,
+            (Self::Arrows, KeyCode::Right) => Action::NextThread,
+            (Self::Arrows, KeyCode::Up) => Action::PreviousLog(1),
+            (Self::Arrows, KeyCode::Down) => Action::NextLog(1),
+
+            (Self::Vim, KeyCode::Char('H')) => Action::PreviousProcess,
+            (Self::Vim, KeyCode::Char('L')) => Action::NextProcess,
+            (Self::Vim, KeyCode::Char('h')) => Action::PreviousThread,
+            (Self::Vim, KeyCode::Char('l')) => Action::NextThread,
+            (Self::Vim, KeyCode::Char('k')) => Action::PreviousLog(1),
+            (Self::Vim, KeyCode::Char('j')) => Action::NextLog(1),
+            (Self::Vim, KeyCode::Char('K')) => Action::PreviousLog(4),
+            (Self::Vim, KeyCode::Char('J')) => Action::NextLog(4),
+            (Self::Vim, KeyCode::Char('g')) => Action::Top,
+            (Self::Vim, KeyCode::Char('G')) => Action::Bottom,
+
+            _ => return None,
+        };
+        Some(action)
+    }
+}
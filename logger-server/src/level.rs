@@ -0,0 +1,115 @@
+//! Decoding of the levels sent by clients.
+//!
+//! [`log::Level`] only has five levels but clients written in other languages may send
+//! severities outside of them. These are clamped into the supported range when decoded so they
+//! never break rendering or sorting:
+//!
+//! - Numbers `1` to `5` are `ERROR`, `WARN`, `INFO`, `DEBUG` and `TRACE` respectively, matching the
+//!   discriminants of [`log::Level`].
+//! - Numbers below `1` are `ERROR` and flagged fatal.
+//! - Numbers above `5` are `TRACE`.
+//! - Names are matched in any case. Besides the names of the five levels, `FATAL`, `CRITICAL`,
+//!   `CRIT`, `PANIC`, `ALERT`, `EMERG` and `EMERGENCY` are `ERROR` and flagged fatal, `ERR` is
+//!   `ERROR`, `WARNING` is `WARN`, `NOTICE` is `INFO` and `VERBOSE`, `FINE`, `FINER`, `FINEST` and
+//!   `ALL` are `TRACE`.
+//! - Other names are rejected.
+
+use serde::Deserialize;
+use tui::style::{Color, Modifier, Style};
+
+/// A level decoded from a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawSeverity")]
+pub struct Severity {
+    pub level: log::Level,
+    /// Whether the client sent a severity above [`log::Level::Error`].
+    pub fatal: bool,
+}
+
+impl From<log::Level> for Severity {
+    fn from(level: log::Level) -> Self {
+        Self {
+            level,
+            fatal: false,
+        }
+    }
+}
+
+impl Severity {
+    const FATAL: Self = Self {
+        level: log::Level::Error,
+        fatal: true,
+    };
+
+    /// Decodes a numeric severity, where `1` to `5` are the discriminants of [`log::Level`].
+    pub fn from_number(number: i64) -> Self {
+        match number {
+            i64::MIN..=0 => Self::FATAL,
+            1 => log::Level::Error.into(),
+            2 => log::Level::Warn.into(),
+            3 => log::Level::Info.into(),
+            4 => log::Level::Debug.into(),
+            5..=i64::MAX => log::Level::Trace.into(),
+        }
+    }
+
+    /// Decodes a named severity in any case, returning `None` for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Ok(level) = name.parse::<log::Level>() {
+            return Some(level.into());
+        }
+        let severity = match name.to_ascii_uppercase().as_str() {
+            "FATAL" | "CRITICAL" | "CRIT" | "PANIC" | "ALERT" | "EMERG" | "EMERGENCY" => {
+                Self::FATAL
+            }
+            "ERR" => log::Level::Error.into(),
+            "WARNING" => log::Level::Warn.into(),
+            "NOTICE" => log::Level::Info.into(),
+            "VERBOSE" | "FINE" | "FINER" | "FINEST" | "ALL" => log::Level::Trace.into(),
+            _ => return None,
+        };
+        Some(severity)
+    }
+
+    /// The name shown for this severity.
+    pub fn name(self) -> &'static str {
+        if self.fatal {
+            "FATAL"
+        } else {
+            self.level.as_str()
+        }
+    }
+
+    /// The style logs of this severity are shown in, so errors and warnings stand out.
+    pub fn style(self) -> Style {
+        match self.level {
+            log::Level::Error if self.fatal => {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            }
+            log::Level::Error => Style::default().fg(Color::Red),
+            log::Level::Warn => Style::default().fg(Color::Yellow),
+            log::Level::Info => Style::default(),
+            log::Level::Debug | log::Level::Trace => Style::default().fg(Color::DarkGray),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSeverity {
+    Number(i64),
+    Name(String),
+}
+
+impl TryFrom<RawSeverity> for Severity {
+    type Error = String;
+
+    fn try_from(raw: RawSeverity) -> Result<Self, Self::Error> {
+        match raw {
+            RawSeverity::Number(number) => Ok(Self::from_number(number)),
+            RawSeverity::Name(name) => {
+                Self::from_name(&name).ok_or_else(|| format!("unknown level `{name}`"))
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Appending every received log to a file as it arrives, rotating the file by size.
+//!
+//! Writes are buffered and flushed every [`LogFile::FLUSH_INTERVAL`], on rotating and on exit.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use nix::{sys::pthread::Pthread, unistd::Pid};
+
+use crate::{export, Log};
+
+pub struct LogFile {
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// The number of bytes in `file`.
+    size: u64,
+    /// The size past which the file is rotated, if it is rotated.
+    max_size: Option<u64>,
+    /// The number of rotated files kept, named `<path>.1` (the newest) to `<path>.<backups>`.
+    backups: usize,
+}
+
+impl LogFile {
+    /// The longest a written log is buffered for, bounding how much a crash can lose.
+    pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Opens the log file at `path`, appending to it if it already exists.
+    ///
+    /// # Errors
+    ///
+    /// When failing to open the file.
+    pub fn open(path: PathBuf, max_size: Option<u64>, backups: usize) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file: BufWriter::new(file),
+            size,
+            max_size,
+            backups,
+        })
+    }
+
+    /// Appends `log` from the thread `thread` of the process `pid`, first rotating the file if
+    /// the line would take it past its maximum size.
+    ///
+    /// Rotation happens in between whole lines so no log is split across files or lost.
+    ///
+    /// # Errors
+    ///
+    /// When failing to rotate or write to the file.
+    pub fn write(&mut self, pid: Pid, thread: Pthread, log: &Log) -> std::io::Result<()> {
+        let mut line = Vec::new();
+        export::write_line(&mut line, pid, thread, log)?;
+
+        if self
+            .max_size
+            .is_some_and(|max_size| self.size > 0 && self.size + line.len() as u64 > max_size)
+        {
+            self.rotate()?;
+        }
+        self.file.write_all(&line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the buffered logs to the file.
+    ///
+    /// # Errors
+    ///
+    /// When failing to write to the file.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+
+    /// Shifts each rotated file along one, dropping the oldest, and starts a new file.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        if self.backups == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for i in (1..self.backups).rev() {
+                match std::fs::rename(self.backup(i), self.backup(i + 1)) {
+                    Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+            std::fs::rename(&self.path, self.backup(1))?;
+        }
+        self.file = BufWriter::new(File::create(&self.path)?);
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Returns the path of the `i`th newest rotated file.
+    fn backup(&self, i: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{i}"));
+        PathBuf::from(path)
+    }
+}
@@ -2,60 +2,701 @@
 #![allow(clippy::needless_pass_by_value)]
 
 use std::{
-    collections::HashMap,
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque},
+    hash::Hash,
     io::Read,
     mem::size_of,
-    os::unix::net::{UnixListener, UnixStream},
-    sync::{Arc, RwLock},
-    time::Duration,
+    net::{TcpListener, TcpStream},
+    os::{
+        fd::{AsFd, OwnedFd},
+        unix::{
+            io::{FromRawFd, RawFd},
+            net::{UnixListener, UnixStream},
+        },
+    },
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     execute,
+    style::Stylize,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use export::ExportFormat;
+use keymap::{Action, Keymap};
+use level::Severity;
+use log_file::LogFile;
+use logger_wire::{
+    Header, COMPRESSED, CONTEXT_FRAME, CORRELATION_FRAME, KEY_VALUES_FRAME, LOG_FRAME,
+    PROCESS_NAME_FRAME, SAMPLING_FRAME, STATUS_FRAME, THREAD_NAME_FRAME,
+};
 use nix::{
+    errno::Errno,
     sys::{
         epoll::{Epoll, EpollCreateFlags, EpollEvent, EpollFlags},
         pthread::Pthread,
     },
     unistd::Pid,
 };
+use palette::{ColorChoice, Palette};
+use serde::Serialize;
+use session::Session;
+use signal_hook::{
+    consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1},
+    iterator::Signals,
+};
+use timestamp::TimeFormat;
+use trigger::{Rule, Trigger};
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, Borders, List, ListItem, ListState, Row, Table},
+    text::{Span, Spans, Text},
+    widgets::{
+        Block, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Sparkline, Table,
+        TableState,
+    },
     Frame, Terminal,
 };
 
+mod ansi;
+mod clipboard;
+mod command;
+mod export;
+mod flapping;
+mod json;
+mod keymap;
+mod level;
+mod log_file;
+mod palette;
+mod session;
+mod timestamp;
+mod trigger;
+
 const DEFAULT_CAPACITY: usize = 1024;
+/// The number of characters of the latest message previewed in the thread list.
+const PREVIEW_WIDTH: u16 = 24;
+/// How long after a client reports dropping records the status bar warns about it.
+const DROP_WARNING: Duration = Duration::from_secs(5);
+/// Shown in place of an empty message, so it is not mistaken for a missing or corrupted one.
+const EMPTY_MESSAGE: &str = "<empty>";
+
+/// The id of the pseudo-thread of the server holding markers.
+const MARKER_THREAD: Pthread = 0;
+/// The id of the pseudo-thread of the server holding its own logs.
+const SYSTEM_THREAD: Pthread = 1;
+/// The first descriptor passed by systemd socket activation.
+const SD_LISTEN_FDS_START: RawFd = 3;
 
 /// Simple program to greet a person
 #[derive(Debug, Parser)]
 struct Args {
-    #[arg(long, default_value = "/tmp/mp-logger-socket")]
-    socket: String,
+    /// Accepts connections on this Unix socket. May be given multiple times.
+    #[arg(
+        long = "socket",
+        value_name = "PATH",
+        default_value = "/tmp/mp-logger-socket"
+    )]
+    sockets: Vec<String>,
+    /// Accepts connections on this inherited, already bound, listening Unix or TCP socket instead
+    /// of binding `--socket`.
+    ///
+    /// When not given, a socket passed by systemd socket activation is used if present.
+    #[arg(long, value_name = "FD", conflicts_with = "sockets")]
+    listen_fd: Option<RawFd>,
+    /// Also accepts connections on this TCP address, such as `0.0.0.0:9000`, for clients on other
+    /// machines. Connections are not authenticated, so only listen on trusted networks. May be
+    /// given multiple times.
+    #[arg(long = "tcp", value_name = "HOST:PORT")]
+    tcp_addresses: Vec<String>,
+    /// The format of the snapshot written on receiving `SIGUSR1`.
+    #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+    export_format: ExportFormat,
+    /// The format clients send logs in.
+    #[arg(long, value_enum, default_value_t = WireFormat::Binary)]
+    wire_format: WireFormat,
+    /// Removes processes once they have been disconnected for this many seconds.
+    #[arg(long, value_name = "SECONDS")]
+    prune_after: Option<u64>,
+    /// Keeps the raw bytes of each received frame so they can be inspected with `b`.
+    #[arg(long)]
+    keep_raw: bool,
+    /// Doesn't print a summary of the capture on exit.
+    #[arg(long)]
+    quiet: bool,
+    /// Prints each log to stdout as it arrives, in the format of the log file, rather than
+    /// drawing the interface, such as when running under a supervisor or in a container.
+    #[arg(long)]
+    no_tui: bool,
+    /// Draws the interface over the main screen of the terminal rather than its alternate screen,
+    /// for terminals and multiplexers lacking one. Implied when `TERM` is unset or `dumb`.
+    #[arg(long)]
+    no_alt_screen: bool,
+    /// Highlights this keyword wherever it appears in a message, ignoring ASCII case. May be
+    /// given multiple times.
+    #[arg(long = "highlight", value_name = "KEYWORD")]
+    highlights: Vec<String>,
+    /// Keeps only the key-value pairs of logs with this key, dropping the others before they are
+    /// shown, written to the log file or exported. May be given multiple times.
+    #[arg(long = "show-key", value_name = "KEY")]
+    shown_keys: Vec<String>,
+    /// Drops the key-value pairs of logs with this key. May be given multiple times.
+    #[arg(long = "hide-key", value_name = "KEY")]
+    hidden_keys: Vec<String>,
+    /// What to do with messages containing NUL bytes.
+    #[arg(long, value_enum, default_value_t = NulPolicy::Keep)]
+    nul: NulPolicy,
+    /// Appends every received log to this file as it arrives.
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+    /// Rotates the log file once it would grow past this many bytes.
+    #[arg(long, value_name = "BYTES", requires = "log_file")]
+    log_file_max_size: Option<u64>,
+    /// The number of rotated log files kept, the oldest is deleted when rotating past this.
+    #[arg(long, value_name = "COUNT", default_value_t = 3)]
+    log_file_backups: usize,
+    /// Treats a lineage of processes as flapping when this many connect within `--flap-window`.
+    #[arg(long, value_name = "COUNT", default_value_t = 5)]
+    flap_threshold: usize,
+    /// The interval within which `--flap-threshold` connections mark a lineage as flapping.
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    flap_window: u64,
+    /// Keeps the server's own logs, such as connections being accepted, at or above this level in
+    /// the `server` pseudo-process.
+    #[arg(long, value_name = "LEVEL", default_value_t = log::LevelFilter::Off)]
+    system_log: log::LevelFilter,
+    /// Runs the command given after `--` whenever a log matching this rule arrives, see the
+    /// `trigger` module for the rule syntax.
+    #[arg(long, value_name = "RULE", requires = "command")]
+    on_match: Option<Rule>,
+    /// The minimum time between runs of the `--on-match` command.
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    on_match_interval: u64,
+    /// The command run by `--on-match`.
+    #[arg(last = true, value_name = "COMMAND")]
+    command: Vec<String>,
+    /// The colors the interface is drawn with, `auto` detects limited color support from `TERM`
+    /// and `COLORTERM`.
+    #[arg(long, value_enum, default_value_t = Palette::Auto)]
+    palette: Palette,
+    /// When to use colors, in the interface and the summary printed on exit, `auto` uses them when
+    /// writing to a terminal.
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// The initial interval each bar of the volume histogram covers.
+    #[arg(long, value_name = "MILLIS", default_value_t = 1000)]
+    histogram_bucket: u64,
+    /// The most client processes kept at once.
+    #[arg(long, value_name = "N")]
+    max_processes: Option<usize>,
+    /// What is done when a new process logs once `--max-processes` are kept.
+    #[arg(long, value_enum, default_value_t = ProcessLimitPolicy::Evict)]
+    on_max_processes: ProcessLimitPolicy,
+    /// Renders the colors and styles of ANSI SGR sequences in messages rather than escaping them.
+    /// Other escape sequences, such as those moving the cursor, are removed.
+    #[arg(long)]
+    ansi: bool,
+    /// Folds each log with the same level and message as the previous log of its thread into it,
+    /// counting it rather than keeping it. The times of folded logs are lost.
+    #[arg(long)]
+    dedup: bool,
+    /// How many times a second the interface is redrawn while no key is pressed, showing new logs
+    /// and keeping times such as uptimes current.
+    #[arg(
+        long,
+        value_name = "FPS",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u32).range(1..=1000)
+    )]
+    frame_rate: u32,
+    /// The most logs kept for each thread, the oldest are removed to make room for new logs.
+    #[arg(long, value_name = "N", default_value_t = 100_000)]
+    max_logs_per_thread: usize,
+    /// Skips frames whose message is longer than this, even once decompressed, and `json` lines
+    /// longer than this, rather than allocating for them.
+    #[arg(long, value_name = "BYTES", default_value_t = 16 * 1024 * 1024)]
+    max_frame_length: u64,
+    /// The keys navigating processes, threads and logs.
+    #[arg(long, value_enum, default_value_t = Keymap::Wasd)]
+    keys: Keymap,
+    /// How the times of logs are shown in the log pane.
+    #[arg(long, value_enum, default_value_t = TimeFormat::Clock)]
+    time_format: TimeFormat,
+    /// Shows the times of logs in UTC rather than local time.
+    #[arg(long)]
+    utc: bool,
+}
+
+/// The format logs are received in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum WireFormat {
+    /// The format sent by `logger-client`.
+    Binary,
+    /// Newline delimited JSON, see the `json` module.
+    Json,
+}
+
+/// What is done with messages containing NUL bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum NulPolicy {
+    /// Keeps NUL bytes, they are shown escaped as `\0` and exported unchanged.
+    Keep,
+    /// Removes NUL bytes from the message.
+    Strip,
+    /// Discards messages containing NUL bytes.
+    Reject,
+}
+
+impl NulPolicy {
+    /// Applies the policy to `message`, returning `None` when it is discarded.
+    fn apply(self, message: String) -> Option<String> {
+        if !message.contains('\0') {
+            return Some(message);
+        }
+        match self {
+            Self::Keep => Some(message),
+            Self::Strip => Some(message.replace('\0', "")),
+            Self::Reject => None,
+        }
+    }
+}
+
+/// What is done when a new process logs once the most processes are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ProcessLimitPolicy {
+    /// Removes the process disconnected the longest, or the oldest process when all are
+    /// connected, to make room for the new process.
+    Evict,
+    /// Discards the logs of the new process.
+    Refuse,
 }
 
+#[derive(Serialize)]
 struct Process {
+    #[serde(serialize_with = "export::serialize_pid")]
     id: Pid,
     // Maps threads ids to their index in `self.threads`.
+    #[serde(skip)]
     thread_id_map: HashMap<Pthread, usize>,
     threads: Vec<Thread>,
+    /// The number of records the client reports having dropped.
+    dropped: u64,
+    /// When the client last reported dropping more records.
+    #[serde(skip)]
+    last_drop: Option<Instant>,
+    /// The number of logs received at each level, indexed by `level as usize - 1`.
+    level_counts: [usize; 5],
+    /// For each level, indexed by `level as usize - 1`, one in how many records the client sends.
+    sampling: [u32; 5],
+    /// The time of the most recent log.
+    last_seen: Duration,
+    /// When the first log from the process was received.
+    #[serde(skip)]
+    first_seen: Instant,
+    /// The process grouping restarts of this process, see [`flapping`], the process itself until
+    /// it connects from this machine.
+    #[serde(skip)]
+    lineage: Pid,
+    /// The number of open connections the process has logged over.
+    #[serde(skip)]
+    connections: usize,
+    /// When the last open connection of the process closed.
+    #[serde(skip)]
+    disconnected_at: Option<Instant>,
 }
 
+#[derive(Serialize)]
 struct Thread {
     id: Pthread,
-    log: Vec<Log>,
+    log: VecDeque<Log>,
+    /// The index of `log[0]` among all logs received from the thread.
+    ///
+    /// Indices into the thread's logs are relative to every log received so they continue to
+    /// refer to the same log when older logs are removed.
+    #[serde(skip)]
+    first: usize,
 }
 
+#[derive(Serialize)]
 struct Log {
     time: Duration,
+    /// The position of the log among those sent by its process, ordering logs sent at the same
+    /// time, `None` when the client did not number it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sequence: Option<u64>,
     level: log::Level,
+    /// Whether the client sent a severity above [`log::Level::Error`], see [`level`].
+    fatal: bool,
     message: String,
+    /// The number of logs received in a row this log stands for, more than `1` once repeats have
+    /// been folded into it, see [`App::dedup`].
+    #[serde(skip_serializing_if = "is_one")]
+    count: u32,
+    /// The id the client attached to the log to follow a request across processes and threads.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation: Option<String>,
+    /// The target of the log, its module path unless given explicitly as in
+    /// `error!(target: "auth", ..)`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    /// The module the log was made in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module_path: Option<String>,
+    /// The source file the log was made in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    /// The line of `file` the log was made on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+    /// The structured key-value pairs of the log, such as `user = 42` in
+    /// `info!(user = 42; "hello")`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    key_values: Vec<(String, String)>,
+    /// The bytes the log was decoded from, only kept with `--keep-raw`.
+    #[serde(skip)]
+    raw: Option<RawFrame>,
+}
+
+/// Returns the time `nanos` past `secs` seconds since the Unix epoch, or `None` when it is too far
+/// in the future to represent, which only a misbehaving client sends.
+fn log_time(secs: u64, nanos: u32) -> Option<Duration> {
+    Duration::from_secs(secs).checked_add(Duration::from_nanos(u64::from(nanos)))
+}
+
+/// Returns the level after `level` in hiding the most verbose level still shown when `raise`,
+/// otherwise in showing the most severe level hidden.
+fn shift_level(level: log::Level, raise: bool) -> log::Level {
+    use log::Level::{Debug, Error, Info, Trace, Warn};
+    match (level, raise) {
+        (Trace, true) | (Info, false) => Debug,
+        (Debug, true) | (Warn, false) => Info,
+        (Info, true) | (Error, false) => Warn,
+        (Warn | Error, true) => Error,
+        (Debug | Trace, false) => Trace,
+    }
+}
+
+/// Sorts `logs`, each from the process and of the log given by `key`, into the order merged views
+/// show them: the logs of each process in the order it sent them, by sequence number, and those of
+/// different processes by time.
+///
+/// Clocks may step backwards, so a later log of a process may have an earlier time. Logs without a
+/// sequence number are sorted by time, and the sorts are stable so those with equal times remain
+/// in the order they were received.
+fn sort_logs<T, P: Hash + Eq>(logs: &mut Vec<T>, key: impl Fn(&T) -> (P, &Log)) {
+    // The logs of each process, in the order the processes first appear.
+    let mut runs = Vec::<Vec<T>>::new();
+    let mut run_indices = HashMap::new();
+    for item in logs.drain(..) {
+        let run = *run_indices.entry(key(&item).0).or_insert_with(|| {
+            runs.push(Vec::new());
+            runs.len() - 1
+        });
+        runs[run].push(item);
+    }
+    for run in &mut runs {
+        run.sort_by_key(|item| {
+            let log = key(item).1;
+            (log.sequence, log.time)
+        });
+    }
+
+    // Merges the runs by the time of their next log, the earliest first.
+    let mut runs = runs
+        .into_iter()
+        .map(|run| run.into_iter().peekable())
+        .collect::<Vec<_>>();
+    let mut next = runs
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(i, run)| Some(Reverse((key(run.peek()?).1.time, i))))
+        .collect::<BinaryHeap<_>>();
+    while let Some(Reverse((_, i))) = next.pop() {
+        logs.extend(runs[i].next());
+        if let Some(item) = runs[i].peek() {
+            next.push(Reverse((key(item).1.time, i)));
+        }
+    }
+}
+
+/// Returns whether `count` is `1`, leaving [`Log::count`] out of exports unless logs were folded.
+#[allow(clippy::trivially_copy_pass_by_ref)] // `serde` passes fields by reference.
+fn is_one(count: &u32) -> bool {
+    *count == 1
+}
+
+/// The bytes a log was decoded from.
+struct RawFrame {
+    header: Vec<u8>,
+    payload: Vec<u8>,
+}
+
+impl Process {
+    /// The approximate number of bytes used by a process excluding its threads.
+    const FOOTPRINT: usize = size_of::<Self>() + size_of::<(Pid, usize)>();
+
+    /// Returns the number of logs received at `level`.
+    fn count(&self, level: log::Level) -> usize {
+        self.level_counts[level as usize - 1]
+    }
+
+    /// Returns the number of logs received.
+    fn total(&self) -> usize {
+        self.level_counts.iter().sum()
+    }
+
+    /// Returns how long the process has been logging for, up until it disconnected.
+    fn uptime(&self) -> Duration {
+        self.disconnected_at
+            .unwrap_or_else(Instant::now)
+            .saturating_duration_since(self.first_seen)
+    }
+
+    /// Returns the approximate number of bytes used by the process including its threads.
+    fn footprint(&self) -> usize {
+        Self::FOOTPRINT
+            + self
+                .threads
+                .iter()
+                .map(|thread| {
+                    Thread::FOOTPRINT + thread.log.iter().map(Log::footprint).sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+}
+
+impl Thread {
+    /// The approximate number of bytes used by a thread excluding its logs.
+    const FOOTPRINT: usize = size_of::<Self>() + size_of::<(Pthread, usize)>();
+
+    /// Returns the index of the latest log, see [`Thread::first`].
+    fn last(&self) -> usize {
+        self.first + self.log.len().saturating_sub(1)
+    }
+
+    /// Returns the log at `index`, see [`Thread::first`].
+    fn get(&self, index: usize) -> Option<&Log> {
+        index
+            .checked_sub(self.first)
+            .and_then(|index| self.log.get(index))
+    }
+}
+
+impl Log {
+    fn severity(&self) -> Severity {
+        Severity {
+            level: self.level,
+            fatal: self.fatal,
+        }
+    }
+
+    /// Returns the target of the log when it was given explicitly, differing from its module path.
+    fn explicit_target(&self) -> Option<&str> {
+        self.target
+            .as_deref()
+            .filter(|&target| Some(target) != self.module_path.as_deref())
+    }
+
+    /// Returns whether `log` repeats this log, with the same level and message, so it can be
+    /// folded into it.
+    fn is_repeated_by(&self, log: &Log) -> bool {
+        self.level == log.level && self.fatal == log.fatal && self.message == log.message
+    }
+
+    /// Returns whether the target of the log is `target` or nested in it, as `net::tcp` is in
+    /// `net`.
+    fn has_target(&self, target: &str) -> bool {
+        self.target.as_deref().is_some_and(|own| {
+            own.strip_prefix(target)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+    }
+
+    /// Returns where the log was made, as `file:line`, or `None` if the client didn't say.
+    fn location(&self) -> Option<String> {
+        let file = self.file.as_deref()?;
+        Some(match self.line {
+            Some(line) => format!("{file}:{line}"),
+            None => String::from(file),
+        })
+    }
+
+    /// The approximate number of bytes used by this log.
+    fn footprint(&self) -> usize {
+        size_of::<Self>()
+            + self.message.capacity()
+            + self.correlation.as_ref().map_or(0, String::capacity)
+            + self.target.as_ref().map_or(0, String::capacity)
+            + self.module_path.as_ref().map_or(0, String::capacity)
+            + self.file.as_ref().map_or(0, String::capacity)
+            + self.key_values.capacity() * size_of::<(String, String)>()
+            + self
+                .key_values
+                .iter()
+                .map(|(key, value)| key.capacity() + value.capacity())
+                .sum::<usize>()
+            + self.raw.as_ref().map_or(0, |raw| {
+                size_of::<RawFrame>() + raw.header.capacity() + raw.payload.capacity()
+            })
+    }
+}
+
+/// The layout drawn in the main area of the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    /// The process, thread and log panes.
+    Detail,
+    /// A table summarizing each process.
+    Overview,
+    /// The logs of every process and thread whose messages contain the searched text.
+    Search,
+}
+
+/// The logs counted by the counts panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CountScope {
+    /// Every log received from every client process.
+    Global,
+    /// The logs kept of the selected thread.
+    Thread,
+}
+
+/// What a row of the detail view selects when clicked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// The process at an index of [`App::processes`].
+    Process(usize),
+    /// The thread at an index of the threads of the process at an index of [`App::processes`].
+    Thread(usize, usize),
+    /// The log at an index of the log pane, scrolled to the top when clicked.
+    Log(usize),
+}
+
+/// The column the overview is sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverviewSort {
+    Pid,
+    Threads,
+    Logs,
+    Errors,
+    Warnings,
+    LastSeen,
+}
+
+impl OverviewSort {
+    fn next(self) -> Self {
+        match self {
+            Self::Pid => Self::Threads,
+            Self::Threads => Self::Logs,
+            Self::Logs => Self::Errors,
+            Self::Errors => Self::Warnings,
+            Self::Warnings => Self::LastSeen,
+            Self::LastSeen => Self::Pid,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Pid => "process",
+            Self::Threads => "threads",
+            Self::Logs => "logs",
+            Self::Errors => "errors",
+            Self::Warnings => "warnings",
+            Self::LastSeen => "last seen",
+        }
+    }
+}
+
+/// What a line of text being entered is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    /// An alias for the process.
+    Alias(Pid),
+    /// A command for the command palette.
+    Command,
+    /// The label of a marker.
+    Marker,
+    /// The text logs in the log pane are filtered by, applied as it is typed.
+    Filter,
+    /// The text searched for in the logs of every process and thread.
+    Search,
+}
+
+impl Prompt {
+    fn title(self) -> &'static str {
+        match self {
+            Self::Alias(_) => "Alias",
+            Self::Command => "Command",
+            Self::Marker => "Marker",
+            Self::Filter => "Filter",
+            Self::Search => "Search",
+        }
+    }
+}
+
+/// A line of text being entered.
+struct Input {
+    prompt: Prompt,
+    text: String,
+}
+
+/// What the logs of the log pane are merged from, see [`App::merged_logs`].
+#[derive(PartialEq)]
+struct MergedKey {
+    /// [`App::generation`] when the logs were merged.
+    generation: u64,
+    correlation: Option<String>,
+    /// The indices of the processes and threads whose logs are merged, empty when merging the
+    /// logs of a correlation.
+    threads: Vec<(usize, usize)>,
+    /// The index of the session the logs are limited to, along with when it stopped.
+    session: Option<(usize, Option<Duration>)>,
+    filter: Option<String>,
+    target_filter: Option<String>,
+    display_level: log::Level,
+    target_levels: BTreeMap<String, log::Level>,
+}
+
+impl MergedKey {
+    /// Returns whether `log` is at or above the most verbose level shown of its target, that set
+    /// for the most specific target it is nested in, otherwise `display_level`.
+    fn shows_level(&self, log: &Log) -> bool {
+        let level = self
+            .target_levels
+            .iter()
+            .filter(|(target, _)| log.has_target(target))
+            .max_by_key(|(target, _)| target.len())
+            .map_or(self.display_level, |(_, &level)| level);
+        log.level <= level
+    }
+}
+
+/// The logs of the log pane as last merged, kept until what they were merged from changes.
+struct Merged {
+    key: MergedKey,
+    /// The indices of the processes and threads of the logs, and their positions among the logs
+    /// kept of their threads, sorted by time.
+    logs: Vec<(usize, usize, usize)>,
+}
+
+/// The results of the search as last listed, kept until the searched text or the logs change.
+struct Searched {
+    /// The searched text, and [`App::generation`] when the logs were searched.
+    key: (String, u64),
+    /// The indices of the processes and threads of the results, and their positions among the
+    /// logs kept of their threads.
+    results: Vec<(usize, usize, usize)>,
 }
 
 struct App {
@@ -64,6 +705,145 @@ struct App {
     process: ListState,
     thread: ListState,
     log: usize,
+    /// Whether the log pane scrolls to keep the latest log in view as logs arrive, until scrolled
+    /// up.
+    follow: bool,
+    /// Whether the log pane sections logs by level rather than listing them chronologically.
+    group_by_level: bool,
+    view: View,
+    overview_sort: OverviewSort,
+    /// Whether the log pane shows the logs of all threads of the selected process together.
+    by_process: bool,
+    /// Whether threads are listed beneath their processes in a single tree, rather than the
+    /// threads of the selected process in a pane of their own.
+    tree: bool,
+    /// The processes whose threads are listed beneath them in the tree.
+    expanded: HashSet<Pid>,
+    /// Whether the row selected in the tree is the selected thread rather than its process.
+    tree_thread: bool,
+    /// Whether the log pane shows the logs of every thread of every process merged by time.
+    timeline: bool,
+    /// Whether the log pane shows the raw bytes of the selected log.
+    show_raw: bool,
+    /// Whether the key-value pairs of logs are listed one per line beneath their messages, rather
+    /// than after them.
+    expand_key_values: bool,
+    /// Whether to keep the raw bytes of each received frame.
+    keep_raw: bool,
+    /// The longest message of a frame, also once decompressed, or `json` line, which is received,
+    /// longer ones are skipped.
+    max_frame_length: u64,
+    /// Keywords highlighted in messages, in ASCII lowercase.
+    highlights: Vec<String>,
+    /// The keys of the key-value pairs which are kept, every key not hidden when empty.
+    shown_keys: HashSet<String>,
+    /// The keys of the key-value pairs which are dropped.
+    hidden_keys: HashSet<String>,
+    /// Whether ANSI SGR sequences in messages are rendered, see [`ansi`].
+    ansi: bool,
+    /// Whether a log with the same level and message as the previous log of its thread is folded
+    /// into it rather than kept.
+    dedup: bool,
+    /// What is done with messages containing NUL bytes.
+    nul_policy: NulPolicy,
+    /// Whether the volume histogram is shown above the log pane.
+    show_histogram: bool,
+    /// The logs counted by level in the panel above the log pane, `None` when it is hidden.
+    counts: Option<CountScope>,
+    /// Where the rows of the detail view were last drawn, to select what is clicked.
+    targets: Vec<(Rect, Target)>,
+    /// Where the log pane, or the search results, were last drawn, to scroll them with the mouse
+    /// wheel and by pages.
+    log_pane: Rect,
+    /// The text searched for in the logs of every process and thread in lowercase, see
+    /// [`View::Search`].
+    search: Option<String>,
+    /// The position of the selected result among [`App::search_results`].
+    search_result: usize,
+    /// How the times of logs are shown in the log pane.
+    time_format: TimeFormat,
+    /// Whether the times of logs are shown in UTC rather than local time.
+    utc: bool,
+    /// When the server started, since the Unix epoch like the times of logs.
+    started: Duration,
+    /// The interval each bar of the volume histogram covers.
+    histogram_bucket: Duration,
+    /// Names given to processes, displayed in place of their ids.
+    aliases: HashMap<Pid, String>,
+    /// The names clients gave their threads.
+    thread_names: HashMap<(Pid, Pthread), String>,
+    /// The names of client processes, as they report them.
+    process_names: HashMap<Pid, String>,
+    /// The key-value pairs describing client processes, as they report them.
+    process_contexts: HashMap<Pid, Vec<(String, String)>>,
+    /// The line of text being entered, which takes all key presses while present.
+    input: Option<Input>,
+    /// A message shown in the status bar until the next key press.
+    message: Option<String>,
+    /// Set by the `quit` command to exit.
+    quit: bool,
+    /// Processes listed before all others, in the order they were pinned.
+    pinned_processes: Vec<Pid>,
+    /// Threads listed before all others within their process, in the order they were pinned.
+    pinned_threads: Vec<(Pid, Pthread)>,
+    /// The file every received log is appended to.
+    log_file: Option<LogFile>,
+    /// Whether each log is printed to stdout as it arrives, in aligned columns, with `--no-tui`.
+    print_logs: bool,
+    /// The id of the pseudo-process holding markers and the server's own logs, that of the server
+    /// itself.
+    server: Pid,
+    /// The most verbose level of the server's own logs which are kept.
+    system_log: log::LevelFilter,
+    /// The command run when a matching log arrives.
+    trigger: Option<Trigger>,
+    /// The colors the interface is drawn with.
+    palette: Palette,
+    /// Tracks processes restarting over and over.
+    flapping: flapping::Detector,
+    /// Threads of one process marked for comparison, once two are marked the log pane shows only
+    /// their logs merged by time.
+    compared_threads: Vec<(Pid, Pthread)>,
+    /// The most client processes kept at once.
+    max_processes: Option<usize>,
+    /// What is done when a new process logs once `max_processes` are kept.
+    process_limit_policy: ProcessLimitPolicy,
+    /// The most logs kept for each thread.
+    max_logs_per_thread: usize,
+    /// Processes whose logs have been discarded by [`ProcessLimitPolicy::Refuse`].
+    refused: HashSet<Pid>,
+    /// The correlation id whose logs from every process the log pane shows, merged by time.
+    correlation: Option<String>,
+    /// Every session in the order they were started, only the last may still be running.
+    sessions: Vec<Session>,
+    /// The index in `sessions` of the session the log pane is limited to.
+    session: Option<usize>,
+    /// The text, in lowercase, the messages of the logs in the log pane are limited to containing.
+    filter: Option<String>,
+    /// The target the logs in the log pane are limited to, along with the targets nested in it
+    /// such as `net::tcp` in `net`.
+    target_filter: Option<String>,
+    /// The most verbose level of the logs in the log pane, the others are hidden but kept.
+    display_level: log::Level,
+    /// The most verbose level of the logs of each target, and the targets nested in it, in the
+    /// log pane, overriding `display_level`.
+    target_levels: BTreeMap<String, log::Level>,
+    /// Every target logs have been received from.
+    seen_targets: BTreeSet<String>,
+    /// The position of the selected target among `seen_targets` in the panel setting the level of
+    /// each target, `None` when it is hidden.
+    target_panel: Option<usize>,
+    /// The approximate number of bytes used by `processes`, kept up to date as data is added so
+    /// it never needs to be recomputed by walking every log.
+    memory: usize,
+    /// Incremented whenever logs are added or removed.
+    generation: u64,
+    /// The logs of the log pane as last merged, which is done several times a frame, see
+    /// [`App::merged_logs`].
+    merged: Mutex<Option<Merged>>,
+    /// The results of the search as last listed, which is done every frame while it is shown, see
+    /// [`App::search_results`].
+    searched: Mutex<Option<Searched>>,
 }
 impl App {
     fn new() -> Self {
@@ -73,305 +853,3056 @@ impl App {
             process: ListState::default(),
             thread: ListState::default(),
             log: 0,
+            follow: false,
+            group_by_level: false,
+            view: View::Detail,
+            overview_sort: OverviewSort::Pid,
+            by_process: false,
+            tree: false,
+            expanded: HashSet::new(),
+            tree_thread: false,
+            timeline: false,
+            show_raw: false,
+            expand_key_values: false,
+            keep_raw: false,
+            max_frame_length: u64::MAX,
+            highlights: Vec::new(),
+            shown_keys: HashSet::new(),
+            hidden_keys: HashSet::new(),
+            ansi: false,
+            dedup: false,
+            nul_policy: NulPolicy::Keep,
+            show_histogram: false,
+            counts: None,
+            targets: Vec::new(),
+            log_pane: Rect::default(),
+            search: None,
+            search_result: 0,
+            time_format: TimeFormat::Clock,
+            utc: false,
+            started: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap(),
+            histogram_bucket: Duration::from_secs(1),
+            aliases: HashMap::new(),
+            thread_names: HashMap::new(),
+            process_names: HashMap::new(),
+            process_contexts: HashMap::new(),
+            input: None,
+            message: None,
+            quit: false,
+            pinned_processes: Vec::new(),
+            pinned_threads: Vec::new(),
+            log_file: None,
+            print_logs: false,
+            server: Pid::this(),
+            system_log: log::LevelFilter::Off,
+            trigger: None,
+            palette: Palette::Full,
+            flapping: flapping::Detector::new(Duration::from_secs(10), 5),
+            compared_threads: Vec::new(),
+            max_processes: None,
+            process_limit_policy: ProcessLimitPolicy::Evict,
+            max_logs_per_thread: usize::MAX,
+            refused: HashSet::new(),
+            correlation: None,
+            sessions: Vec::new(),
+            session: None,
+            filter: None,
+            target_filter: None,
+            display_level: log::Level::Trace,
+            target_levels: BTreeMap::new(),
+            seen_targets: BTreeSet::new(),
+            target_panel: None,
+            memory: 0,
+            generation: 0,
+            merged: Mutex::new(None),
+            searched: Mutex::new(None),
         }
     }
 
-    pub fn next_process(&mut self) {
-        if let Some(process) = self.process.selected() {
-            let new_process = (process + 1) % self.processes.len();
-            self.process.select(Some(new_process));
-
-            if !self.processes[new_process].threads.is_empty() {
-                self.thread.select(Some(0));
+    /// Returns the alias of `process`, or else its name, or its id if it has neither.
+    fn process_label(&self, process: &Process) -> String {
+        self.process_name(process.id)
+            .map_or_else(|| format!("{:x}", process.id.as_raw()), String::from)
+    }
 
-                if !self.processes[new_process].threads[0].log.is_empty() {
-                    self.log = 0;
-                }
-            }
+    /// Returns the alias of `process`, or else its name, followed by its id, or just its id if it
+    /// has neither.
+    fn process_full_label(&self, process: &Process) -> String {
+        match self.process_name(process.id) {
+            Some(name) => format!("{name} ({:x})", process.id.as_raw()),
+            None => format!("{:x}", process.id.as_raw()),
         }
     }
 
-    pub fn previous_process(&mut self) {
-        if let Some(process) = self.process.selected() {
-            let new_process = if process > 0 {
-                process - 1
-            } else {
-                self.processes.len() - 1
-            };
-            self.process.select(Some(new_process));
-
-            if !self.processes[new_process].threads.is_empty() {
-                self.thread.select(Some(0));
+    /// Returns the alias of the process `pid`, or else the name it reported.
+    fn process_name(&self, pid: Pid) -> Option<&str> {
+        self.aliases
+            .get(&pid)
+            .or_else(|| self.process_names.get(&pid))
+            .map(String::as_str)
+    }
 
-                if !self.processes[new_process].threads[0].log.is_empty() {
-                    self.log = 0;
-                }
-            }
+    /// Returns the name of the thread `thread` of the process `pid` followed by its id, or just
+    /// its id if it has no name.
+    fn thread_label(&self, pid: Pid, thread: Pthread) -> String {
+        match self.thread_names.get(&(pid, thread)) {
+            Some(name) => format!("{name} ({thread:x})"),
+            None => format!("{thread:x}"),
         }
     }
 
-    pub fn next_thread(&mut self) {
-        if let Some(thread) = self.thread.selected() {
-            let new_thread = (thread + 1)
-                % self.processes[self.process.selected().unwrap()]
-                    .threads
-                    .len();
-            self.thread.select(Some(new_thread));
-
-            if !self.processes[self.process.selected().unwrap()].threads[new_thread]
-                .log
-                .is_empty()
-            {
-                self.log = 0;
-            }
+    /// Starts entering an alias for the selected process.
+    pub fn start_alias(&mut self) {
+        self.clamp_selection();
+        if let Some(process) = self.process.selected() {
+            let pid = self.processes[process].id;
+            self.input = Some(Input {
+                prompt: Prompt::Alias(pid),
+                text: self.aliases.get(&pid).cloned().unwrap_or_default(),
+            });
         }
     }
 
-    pub fn previous_thread(&mut self) {
-        if let Some(thread) = self.thread.selected() {
-            let new_thread = if thread > 0 {
-                thread - 1
-            } else {
-                self.processes[self.process.selected().unwrap()]
-                    .threads
-                    .len()
-                    - 1
-            };
-            self.thread.select(Some(new_thread));
+    pub fn start_marker(&mut self) {
+        self.input = Some(Input {
+            prompt: Prompt::Marker,
+            text: String::new(),
+        });
+    }
 
-            if !self.processes[self.process.selected().unwrap()].threads[new_thread]
-                .log
-                .is_empty()
-            {
-                self.log = 0;
-            }
-        }
+    /// Adds a log labelled `label` at the current time to the markers thread, which is shown
+    /// alongside the logs of every merged view.
+    fn add_marker(&mut self, label: String) {
+        self.add_server_log(MARKER_THREAD, log::Level::Info, label);
     }
 
-    pub fn next_log(&mut self, n: usize) {
-        if let (Some(process), Some(thread)) = (self.process.selected(), self.thread.selected()) {
-            self.log = std::cmp::min(
-                self.log + n,
-                self.processes[process].threads[thread].log.len() - 1,
-            );
+    /// Adds an event of the server itself to the system thread, if `level` is enabled by
+    /// `--system-log`.
+    fn system_log(&mut self, level: log::Level, message: String) {
+        if level <= self.system_log {
+            self.add_server_log(SYSTEM_THREAD, level, message);
         }
     }
 
-    pub fn previous_log(&mut self, n: usize) {
-        self.log = self.log.checked_sub(n).unwrap_or(0);
+    /// Adds a log at the current time to the thread `thread` of the server pseudo-process.
+    fn add_server_log(&mut self, thread: Pthread, level: log::Level, message: String) {
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let log = Log {
+            time,
+            sequence: None,
+            level,
+            fatal: false,
+            message,
+            count: 1,
+            correlation: None,
+            target: None,
+            module_path: None,
+            file: None,
+            line: None,
+            key_values: Vec::new(),
+            raw: None,
+        };
+        self.aliases
+            .entry(self.server)
+            .or_insert_with(|| String::from("server"));
+        self.add_log(self.server, thread, log);
     }
-}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+    pub fn start_filter(&mut self) {
+        self.input = Some(Input {
+            prompt: Prompt::Filter,
+            text: self.filter.clone().unwrap_or_default(),
+        });
+    }
 
-    // setup terminal
-    enable_raw_mode()?;
-    let mut log = std::io::stdout();
-    execute!(log, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(log);
-    let mut terminal = Terminal::new(backend)?;
+    /// Limits the log pane to logs whose messages contain `text` ignoring case, or shows every log
+    /// again when `text` is empty.
+    fn set_filter(&mut self, text: &str) {
+        self.filter = (!text.is_empty()).then(|| text.to_lowercase());
+        self.scroll_to_top();
+    }
 
-    // create app and run it
-    let app = App::new();
-    let res = run_app(&mut terminal, app, args.socket);
+    pub fn start_search(&mut self) {
+        self.input = Some(Input {
+            prompt: Prompt::Search,
+            text: self.search.clone().unwrap_or_default(),
+        });
+    }
 
-    // restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    /// Lists the logs of every process and thread whose messages contain `text` ignoring case, or
+    /// returns to the log pane when `text` is empty.
+    fn set_search(&mut self, text: &str) {
+        self.search = (!text.is_empty()).then(|| text.to_lowercase());
+        self.search_result = 0;
+        self.view = if self.search.is_some() {
+            View::Search
+        } else {
+            View::Detail
+        };
+    }
 
-    if let Err(err) = res {
-        println!("{err:?}");
+    /// Hides the most verbose level still shown in the log pane when `raise`, otherwise shows the
+    /// most severe level hidden.
+    pub fn shift_display_level(&mut self, raise: bool) {
+        self.display_level = shift_level(self.display_level, raise);
+        self.scroll_to_top();
     }
 
-    Ok(())
-}
+    /// Shows or hides the panel setting the level of each target.
+    pub fn toggle_target_panel(&mut self) {
+        self.target_panel = match self.target_panel {
+            Some(_) => None,
+            None => Some(0),
+        };
+    }
 
-fn run_app<B: Backend>(
-    terminal: &mut Terminal<B>,
-    app: App,
-    socket: String,
-) -> std::io::Result<()> {
-    let app = Arc::new(RwLock::new(app));
+    /// Selects the target `n` after the selected one in the target panel.
+    pub fn next_target(&mut self, n: usize) {
+        if let Some(selected) = &mut self.target_panel {
+            *selected = selected
+                .saturating_add(n)
+                .min(self.seen_targets.len().saturating_sub(1));
+        }
+    }
 
-    let app_clone = app.clone();
-    let socket_clone = socket.clone();
-    let _ = std::thread::spawn(move || {
-        let listener = UnixListener::bind(&socket_clone).unwrap();
-        for (stream, id) in listener.incoming().zip(0..) {
-            // println!("Received connection: {id:08x}");
+    /// Selects the target `n` before the selected one in the target panel.
+    pub fn previous_target(&mut self, n: usize) {
+        if let Some(selected) = &mut self.target_panel {
+            *selected = selected.saturating_sub(n);
+        }
+    }
+
+    /// Returns the target selected in the target panel.
+    fn selected_target(&self) -> Option<&String> {
+        self.seen_targets.iter().nth(self.target_panel?)
+    }
 
-            let stream = stream.unwrap();
-            let app_clone_clone = app_clone.clone();
-            std::thread::spawn(move || handle_stream(stream, id, app_clone_clone));
+    /// Hides the most verbose level still shown of the selected target when `raise`, otherwise
+    /// shows the most severe level hidden, starting from `display_level`.
+    pub fn shift_target_level(&mut self, raise: bool) {
+        if let Some(target) = self.selected_target().cloned() {
+            let level = self
+                .target_levels
+                .get(&target)
+                .copied()
+                .unwrap_or(self.display_level);
+            self.target_levels.insert(target, shift_level(level, raise));
+            self.scroll_to_top();
         }
-    });
+    }
 
-    loop {
-        let app_clone = app.clone();
-        terminal.draw(|f| ui(f, app_clone))?;
+    /// Shows the selected target at `display_level` again.
+    pub fn clear_target_level(&mut self) {
+        if let Some(target) = self.selected_target().cloned() {
+            self.target_levels.remove(&target);
+            self.scroll_to_top();
+        }
+    }
 
-        if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Char('q') => break,
-                KeyCode::Char('w') => app.write().unwrap().previous_process(),
-                KeyCode::Char('s') => app.write().unwrap().next_process(),
-                KeyCode::Char('e') => app.write().unwrap().previous_thread(),
-                KeyCode::Char('d') => app.write().unwrap().next_thread(),
-                KeyCode::Char('r') => app.write().unwrap().previous_log(1),
-                KeyCode::Char('f') => app.write().unwrap().next_log(1),
-                KeyCode::Char('t') => app.write().unwrap().previous_log(2),
-                KeyCode::Char('g') => app.write().unwrap().next_log(2),
-                KeyCode::Char('y') => app.write().unwrap().previous_log(4),
-                KeyCode::Char('h') => app.write().unwrap().next_log(4),
-                KeyCode::Char('u') => app.write().unwrap().log = 0,
-                KeyCode::Char('j') => {
-                    let mut guard = app.write().unwrap();
-                    if let (Some(process), Some(thread)) =
-                        (guard.process.selected(), guard.thread.selected())
-                    {
-                        guard.log = guard.processes[process].threads[thread].log.len() - 1;
+    pub fn start_command(&mut self) {
+        self.input = Some(Input {
+            prompt: Prompt::Command,
+            text: String::new(),
+        });
+    }
+
+    /// Applies a key press to the line of text being entered.
+    fn handle_input(&mut self, code: KeyCode) {
+        let Some(input) = &mut self.input else {
+            return;
+        };
+        match code {
+            KeyCode::Char(c) => input.text.push(c),
+            KeyCode::Tab if input.prompt == Prompt::Command => command::complete(&mut input.text),
+            KeyCode::Backspace => {
+                input.text.pop();
+            }
+            KeyCode::Esc => {
+                if input.prompt == Prompt::Filter {
+                    self.filter = None;
+                }
+                self.input = None;
+            }
+            KeyCode::Enter => {
+                let Input { prompt, text } = self.input.take().unwrap();
+                match prompt {
+                    Prompt::Alias(pid) => {
+                        if text.is_empty() {
+                            self.aliases.remove(&pid);
+                        } else {
+                            self.aliases.insert(pid, text);
+                        }
+                    }
+                    Prompt::Command => {
+                        self.message = command::run(self, &text).unwrap_or_else(Some);
                     }
+                    Prompt::Marker => self.add_marker(text),
+                    Prompt::Filter => self.set_filter(&text),
+                    Prompt::Search => self.set_search(&text),
                 }
-                _ => {}
             }
+            _ => {}
+        }
+        // The filter is applied as it is typed.
+        if let Some(text) = self
+            .input
+            .as_ref()
+            .filter(|input| input.prompt == Prompt::Filter)
+            .map(|input| input.text.clone())
+        {
+            self.set_filter(&text);
         }
     }
 
-    std::fs::remove_file(&socket).unwrap();
-
-    Ok(())
-}
-
-fn ui<B: Backend>(f: &mut Frame<B>, app: Arc<RwLock<App>>) {
-    let mut app = app.write().unwrap();
-
-    let size = f.size();
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(
-            [
-                Constraint::Length(9),
-                Constraint::Length(14),
-                Constraint::Min(0),
-            ]
-            .as_ref(),
-        )
-        .split(size);
-
-    let block = Block::default().style(Style::default());
-    f.render_widget(block, size);
-
-    // Process
-    // ---------------------------------------------------------------------------------------------
-    let process_numbers = app
-        .processes
-        .iter()
-        .map(|t| ListItem::new(format!("{:x}", t.id.as_raw())))
-        .collect::<Vec<_>>();
-
-    let process_tabs = List::new(process_numbers)
-        .block(Block::default().borders(Borders::ALL).title("Process"))
-        .highlight_style(
-            Style::default()
-                .add_modifier(Modifier::BOLD)
-                .fg(Color::Cyan),
-        );
+    /// Returns the indices of `self.processes` in the order they are listed in the overview.
+    fn overview_order(&self) -> Vec<usize> {
+        let processes = &self.processes;
+        let mut order = self.listed_processes();
+        match self.overview_sort {
+            OverviewSort::Pid => order.sort_by_key(|&i| processes[i].id.as_raw()),
+            OverviewSort::Threads => order.sort_by_key(|&i| Reverse(processes[i].threads.len())),
+            OverviewSort::Logs => order.sort_by_key(|&i| Reverse(processes[i].total())),
+            OverviewSort::Errors => {
+                order.sort_by_key(|&i| Reverse(processes[i].count(log::Level::Error)));
+            }
+            OverviewSort::Warnings => {
+                order.sort_by_key(|&i| Reverse(processes[i].count(log::Level::Warn)));
+            }
+            OverviewSort::LastSeen => order.sort_by_key(|&i| Reverse(processes[i].last_seen)),
+        }
+        self.pin_processes_first(&mut order);
+        order
+    }
 
-    f.render_stateful_widget(process_tabs, chunks[0], &mut app.process);
+    /// Returns the indices of `self.processes` in the order they are listed.
+    fn process_order(&self) -> Vec<usize> {
+        let mut order = self.listed_processes();
+        self.pin_processes_first(&mut order);
+        order
+    }
 
-    // Thread
-    // ---------------------------------------------------------------------------------------------
-    let thread_ids = if let Some(process) = app.process.selected() {
-        app.processes[process]
-            .threads
+    /// Returns the indices of the processes which are listed, in the order they were first seen.
+    ///
+    /// Of each lineage which has been flapping only the newest process is listed, along with any
+    /// pinned or selected processes.
+    fn listed_processes(&self) -> Vec<usize> {
+        let newest = self
+            .processes
             .iter()
-            .map(|thread| ListItem::new(format!("{:x}", thread.id)))
+            .enumerate()
+            .map(|(i, process)| (process.lineage, i))
+            .collect::<HashMap<_, _>>();
+        (0..self.processes.len())
+            .filter(|&i| {
+                let process = &self.processes[i];
+                !self.flapping.has_flapped(process.lineage)
+                    || newest[&process.lineage] == i
+                    || self.pinned_processes.contains(&process.id)
+                    || self.process.selected() == Some(i)
+            })
             .collect()
-    } else {
-        Vec::new()
-    };
+    }
 
-    let thread_tabs = List::new(thread_ids)
-        .block(Block::default().borders(Borders::ALL).title("Thread"))
-        .highlight_style(
-            Style::default()
-                .add_modifier(Modifier::BOLD)
-                .fg(Color::Cyan),
-        );
+    /// Returns the marker shown before `process` in lists, noting whether it is pinned, whether
+    /// it stands in for a flapping lineage and whether it has dropped records.
+    fn process_marker(&self, process: &Process) -> String {
+        let mut marker = String::new();
+        if self.pinned_processes.contains(&process.id) {
+            marker.push('*');
+        }
+        if self.flapping.has_flapped(process.lineage) {
+            marker.push('~');
+        }
+        if process.dropped != 0 {
+            marker.push('!');
+        }
+        marker
+    }
 
-    f.render_stateful_widget(thread_tabs, chunks[1], &mut app.thread);
+    /// Moves the pinned processes in `order` to the front, in the order they were pinned.
+    fn pin_processes_first(&self, order: &mut [usize]) {
+        // The sort is stable so unpinned processes keep their order.
+        order.sort_by_key(|&i| {
+            self.pinned_processes
+                .iter()
+                .position(|&pid| pid == self.processes[i].id)
+                .unwrap_or(usize::MAX)
+        });
+    }
 
-    // log
-    // ---------------------------------------------------------------------------------------------
-    let log = match (app.process.selected(), app.thread.selected()) {
-        (Some(process), Some(thread)) => {
-            let x = app.processes[process].threads[thread]
-                .log
+    /// Returns the indices of the threads of `process` in the order they are listed.
+    fn thread_order(&self, process: usize) -> Vec<usize> {
+        let process = &self.processes[process];
+        let mut order = (0..process.threads.len()).collect::<Vec<_>>();
+        // The sort is stable so unpinned threads keep their order.
+        order.sort_by_key(|&i| {
+            self.pinned_threads
                 .iter()
-                .enumerate()
-                .skip(app.log)
-                .map(
-                    |(
-                        i,
-                        Log {
-                            time,
-                            message,
-                            level,
-                        },
-                    )| {
-                        Row::new(vec![
-                            format!("{i:08x}"),
-                            time.as_micros().to_string(),
-                            level.to_string(),
-                            message.clone(),
-                        ])
-                    },
-                )
-                .collect::<Vec<_>>();
-            Table::new(x)
-        }
-        _ => Table::new(Vec::new()),
+                .position(|&pinned| pinned == (process.id, process.threads[i].id))
+                .unwrap_or(usize::MAX)
+        });
+        order
     }
-    .block(Block::default().title("Log").borders(Borders::ALL))
-    .header(Row::new(vec!["#", "Time (μs)", "Level", "Message"]))
-    .widths(&[
-        Constraint::Length(8),
-        Constraint::Length(16),
-        Constraint::Length(5),
-        Constraint::Percentage(100),
-    ]);
 
-    f.render_widget(log, chunks[2]);
-}
+    /// Returns the rows of the tree, each a process index along with a thread index for the rows
+    /// of threads listed beneath an expanded process.
+    fn tree_rows(&self) -> Vec<(usize, Option<usize>)> {
+        self.process_order()
+            .into_iter()
+            .flat_map(|process| {
+                let threads = if self.expanded.contains(&self.processes[process].id) {
+                    self.thread_order(process)
+                } else {
+                    Vec::new()
+                };
+                std::iter::once((process, None)).chain(
+                    threads
+                        .into_iter()
+                        .map(move |thread| (process, Some(thread))),
+                )
+            })
+            .collect()
+    }
 
-fn non_blocking(res: std::io::Result<usize>) -> std::io::Result<usize> {
-    match res {
-        Ok(n) => Ok(n),
-        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => Ok(0),
-        Err(err) => Err(err),
+    /// Returns the row selected in the tree, see [`App::tree_rows`].
+    fn tree_row(&self) -> Option<(usize, Option<usize>)> {
+        let process = self.process.selected()?;
+        let thread = self
+            .thread
+            .selected()
+            .filter(|_| self.tree_thread && self.expanded.contains(&self.processes[process].id));
+        Some((process, thread))
     }
-}
 
-#[repr(C)]
-struct LogData {
-    secs: u64,
-    nanos: u32,
-    pid: nix::unistd::Pid,
-    pthread: nix::sys::pthread::Pthread,
-    length: usize,
-    level: log::Level,
+    /// Selects the row after the selected one in the tree when `forward`, or before it otherwise.
+    fn step_tree(&mut self, forward: bool) {
+        let rows = self.tree_rows();
+        if rows.is_empty() {
+            return;
+        }
+        let position = self
+            .tree_row()
+            .and_then(|selected| rows.iter().position(|&row| row == selected))
+            .map_or(0, |position| {
+                if forward {
+                    (position + 1) % rows.len()
+                } else {
+                    position.checked_sub(1).unwrap_or(rows.len() - 1)
+                }
+            });
+        match rows[position] {
+            (process, None) => {
+                if self.process.selected() != Some(process) {
+                    self.select_process(process);
+                }
+                self.tree_thread = false;
+            }
+            (process, Some(thread)) => self.select_thread(process, thread),
+        }
+    }
+
+    /// Selects the thread at index `thread` of the process at index `process`.
+    fn select_thread(&mut self, process: usize, thread: usize) {
+        self.process.select(Some(process));
+        self.thread.select(Some(thread));
+        self.tree_thread = true;
+        self.scroll_to_top();
+    }
+
+    /// Lists or stops listing the threads of the selected process beneath it in the tree.
+    pub fn toggle_expanded(&mut self) {
+        self.clamp_selection();
+        let Some(process) = self.process.selected() else {
+            return;
+        };
+        let pid = self.processes[process].id;
+        if !self.expanded.remove(&pid) {
+            self.expanded.insert(pid);
+        }
+        self.tree_thread = false;
+        if !self.tree {
+            self.message = Some(String::from(
+                "Expanded processes are only shown in the tree, toggled with Z",
+            ));
+        }
+    }
+
+    /// Pins or unpins the selected process.
+    pub fn toggle_pin_process(&mut self) {
+        self.clamp_selection();
+        if let Some(process) = self.process.selected() {
+            let pid = self.processes[process].id;
+            if let Some(position) = self.pinned_processes.iter().position(|&p| p == pid) {
+                self.pinned_processes.remove(position);
+            } else {
+                self.pinned_processes.push(pid);
+            }
+        }
+    }
+
+    /// Pins or unpins the selected thread.
+    pub fn toggle_pin_thread(&mut self) {
+        self.clamp_selection();
+        if let (Some(process), Some(thread)) = (self.process.selected(), self.thread.selected()) {
+            let process = &self.processes[process];
+            let key = (process.id, process.threads[thread].id);
+            if let Some(position) = self.pinned_threads.iter().position(|&p| p == key) {
+                self.pinned_threads.remove(position);
+            } else {
+                self.pinned_threads.push(key);
+            }
+        }
+    }
+
+    /// Returns whether a log from the process `pid` can be added, making room for it when it is
+    /// a new process and the most processes are kept.
+    fn make_room(&mut self, pid: Pid) -> bool {
+        let Some(max_processes) = self.max_processes else {
+            return true;
+        };
+        // The server's own pseudo-process is never limited.
+        if pid == self.server || self.process_id_map.contains_key(&pid) {
+            return true;
+        }
+        let clients = self
+            .processes
+            .iter()
+            .filter(|process| process.id != self.server)
+            .count();
+        if clients < max_processes {
+            return true;
+        }
+
+        let candidates = self.processes.iter().enumerate().filter(|(_, process)| {
+            process.id != self.server && !self.pinned_processes.contains(&process.id)
+        });
+        let evicted = candidates
+            .clone()
+            .filter_map(|(i, process)| process.disconnected_at.map(|at| (at, i)))
+            .min()
+            .map(|(_, i)| i)
+            .or_else(|| {
+                candidates
+                    .min_by_key(|(_, process)| process.first_seen)
+                    .map(|(i, _)| i)
+            });
+        match (self.process_limit_policy, evicted) {
+            (ProcessLimitPolicy::Evict, Some(evicted)) => {
+                let label = self.process_full_label(&self.processes[evicted]);
+                self.remove_process(evicted);
+                self.system_log(
+                    log::Level::Info,
+                    format!(
+                        "Removed {label} to make room for {:x}, see --max-processes",
+                        pid.as_raw()
+                    ),
+                );
+                true
+            }
+            _ => {
+                if self.refused.insert(pid) {
+                    self.system_log(
+                        log::Level::Warn,
+                        format!(
+                            "Discarding the logs of {:x}, see --max-processes",
+                            pid.as_raw()
+                        ),
+                    );
+                }
+                false
+            }
+        }
+    }
+
+    /// Adds `log` to the thread `pthread` of the process `pid`, creating them if needed, returning
+    /// whether it was added rather than discarded under `--max-processes`.
+    ///
+    /// The first log received selects its process and thread.
+    fn add_log(&mut self, pid: Pid, pthread: Pthread, mut log: Log) -> bool {
+        if !self.make_room(pid) {
+            return false;
+        }
+        log.key_values.retain(|(key, _)| self.shows_key(key));
+        if let Some(target) = &log.target {
+            if !self.seen_targets.contains(target) {
+                self.seen_targets.insert(target.clone());
+            }
+        }
+        let time = log.time;
+        let level = log.level;
+        self.memory += log.footprint();
+        self.generation += 1;
+        if let Some(log_file) = &mut self.log_file {
+            if let Err(err) = log_file.write(pid, pthread, &log) {
+                self.message = Some(format!("Failed to write to the log file: {err}"));
+            }
+        }
+        if self.print_logs {
+            // Nothing can be done about a closed stdout, such as a pipe whose reader exited.
+            let _ = export::write_aligned_line(&mut std::io::stdout().lock(), pid, pthread, &log);
+        }
+        // The server's own logs never trigger the command, so a failure to run it cannot
+        // trigger it again.
+        if let Some(trigger) = self.trigger.as_mut().filter(|_| pid != self.server) {
+            match trigger.fire(pid, pthread, &log) {
+                Ok(true) => self.system_log(
+                    log::Level::Info,
+                    format!("Ran the --on-match command for a log from {pid}"),
+                ),
+                Ok(false) => {}
+                Err(err) => self.system_log(
+                    log::Level::Warn,
+                    format!("Failed to run the --on-match command: {err}"),
+                ),
+            }
+        }
+
+        if let Some(process) = self
+            .process_id_map
+            .get(&pid)
+            .copied()
+            .map(|i| &mut self.processes[i])
+        {
+            if let Some(thread) = process
+                .thread_id_map
+                .get(&pthread)
+                .map(|i| &mut process.threads[*i])
+            {
+                match thread.log.back_mut() {
+                    Some(last) if self.dedup && last.is_repeated_by(&log) => {
+                        last.count = last.count.saturating_add(1);
+                        // The folded log is not kept.
+                        self.memory -= log.footprint();
+                    }
+                    _ => thread.log.push_back(log),
+                }
+                // Indices are relative to every log received, so those of the remaining logs
+                // are unchanged, see `Thread::first`.
+                while thread.log.len() > self.max_logs_per_thread {
+                    if let Some(removed) = thread.log.pop_front() {
+                        self.memory -= removed.footprint();
+                        thread.first += 1;
+                    }
+                }
+            } else {
+                process.thread_id_map.insert(pthread, process.threads.len());
+                process.threads.push(Thread {
+                    id: pthread,
+                    log: VecDeque::from([log]),
+                    first: 0,
+                });
+                self.memory += Thread::FOOTPRINT;
+            }
+        } else {
+            self.memory += Process::FOOTPRINT + Thread::FOOTPRINT;
+            let len = self.processes.len();
+            self.process_id_map.insert(pid, len);
+            self.processes.push(Process {
+                id: pid,
+                thread_id_map: std::iter::once((pthread, 0)).collect(),
+                threads: vec![Thread {
+                    id: pthread,
+                    log: VecDeque::from([log]),
+                    first: 0,
+                }],
+                dropped: 0,
+                last_drop: None,
+                level_counts: [0; 5],
+                sampling: [1; 5],
+                last_seen: Duration::ZERO,
+                first_seen: Instant::now(),
+                lineage: pid,
+                connections: 0,
+                disconnected_at: None,
+            });
+        }
+        let index = self.process_id_map[&pid];
+        let process = &mut self.processes[index];
+        process.level_counts[level as usize - 1] += 1;
+        process.last_seen = process.last_seen.max(time);
+        // Connections are numbered in the order they were accepted, not the order their processes
+        // were added in, so the process is selected by where it was stored.
+        if self.thread.selected().is_none() {
+            let thread = process.thread_id_map[&pthread];
+            self.process.select(Some(index));
+            self.thread.select(Some(thread));
+        }
+        true
+    }
+
+    /// Returns whether the key-value pairs with `key` are kept, see `--show-key` and `--hide-key`.
+    fn shows_key(&self, key: &str) -> bool {
+        (self.shown_keys.is_empty() || self.shown_keys.contains(key))
+            && !self.hidden_keys.contains(key)
+    }
+
+    /// Records a connection the process `pid` has logged over being opened, `local` when it was
+    /// accepted on a Unix socket.
+    ///
+    /// The lineage of a process is looked up when it first connects, and only over a Unix socket,
+    /// since the process ids of clients on other machines are not those of processes in `/proc`.
+    fn connect(&mut self, pid: Pid, local: bool) {
+        if let Some(&process) = self.process_id_map.get(&pid) {
+            let process = &mut self.processes[process];
+            if local && process.connections == 0 && process.disconnected_at.is_none() {
+                process.lineage = flapping::lineage(pid);
+            }
+            process.connections += 1;
+            process.disconnected_at = None;
+            self.flapping.connect(process.lineage);
+        }
+    }
+
+    /// Records a connection the processes `pids` have logged over being closed.
+    fn disconnect(&mut self, pids: &HashSet<Pid>) {
+        for pid in pids {
+            if let Some(&process) = self.process_id_map.get(pid) {
+                let process = &mut self.processes[process];
+                // A process removed to make room and added again on the same connection was
+                // never connected again.
+                process.connections = process.connections.saturating_sub(1);
+                if process.connections == 0 {
+                    process.disconnected_at = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    /// Removes processes which have been disconnected for at least `grace`.
+    fn prune_disconnected(&mut self, grace: Duration) {
+        let mut i = 0;
+        while i < self.processes.len() {
+            if self.processes[i]
+                .disconnected_at
+                .is_some_and(|disconnected_at| disconnected_at.elapsed() >= grace)
+            {
+                self.remove_process(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Removes the process at `index` in `self.processes`, keeping the selection on the same
+    /// process where it is not the one removed.
+    fn remove_process(&mut self, index: usize) {
+        let process = self.processes.remove(index);
+        self.generation += 1;
+        self.memory -= process.footprint();
+        self.process_id_map.remove(&process.id);
+        self.compared_threads.retain(|&(pid, _)| pid != process.id);
+        for i in self.process_id_map.values_mut() {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+
+        match self.process.selected() {
+            Some(selected) if selected == index => {
+                if self.processes.is_empty() {
+                    self.process.select(None);
+                    self.thread.select(None);
+                    self.scroll_to_top();
+                } else {
+                    self.select_process(index.min(self.processes.len() - 1));
+                }
+            }
+            Some(selected) if selected > index => self.process.select(Some(selected - 1)),
+            _ => {}
+        }
+    }
+
+    /// Clamps the selection to the current processes, threads and logs.
+    ///
+    /// Processes are added by other threads while a selection is held, so it must be clamped
+    /// before being used to index.
+    fn clamp_selection(&mut self) {
+        let process = self
+            .process
+            .selected()
+            .filter(|_| !self.processes.is_empty())
+            .map(|process| process.min(self.processes.len() - 1));
+        self.process.select(process);
+
+        let threads = process.map_or(&[][..], |process| &self.processes[process].threads[..]);
+        let thread = self
+            .thread
+            .selected()
+            .filter(|_| !threads.is_empty())
+            .map(|thread| thread.min(threads.len() - 1));
+        self.thread.select(thread);
+
+        self.log = self
+            .log_bounds()
+            .map_or(0, |(first, last)| self.log.clamp(first, last));
+    }
+
+    /// Returns the threads whose logs are merged in the log pane along with the ids of their
+    /// processes, or `None` when it shows the logs of the selected thread alone.
+    fn merged_threads(&self) -> Option<Vec<(Pid, &Thread)>> {
+        let thread = |(pid, pthread)| {
+            let process = &self.processes[*self.process_id_map.get(&pid)?];
+            Some((
+                pid,
+                process.threads.get(*process.thread_id_map.get(&pthread)?)?,
+            ))
+        };
+        let mut threads = if self.timeline {
+            self.processes
+                .iter()
+                .flat_map(|process| {
+                    process
+                        .threads
+                        .iter()
+                        .map(move |thread| (process.id, thread))
+                })
+                .collect()
+        } else if let [first, second] = self.compared_threads[..] {
+            [first, second]
+                .into_iter()
+                .filter_map(thread)
+                .collect::<Vec<_>>()
+        } else if self.by_process {
+            let process = &self.processes[self.process.selected()?];
+            process
+                .threads
+                .iter()
+                .map(|thread| (process.id, thread))
+                .collect()
+        } else {
+            return None;
+        };
+        // Markers anchor the timeline of every merged view.
+        if let Some(markers) = thread((self.server, MARKER_THREAD)) {
+            if !threads
+                .iter()
+                .any(|&(_, thread)| std::ptr::eq(thread, markers.1))
+            {
+                threads.push(markers);
+            }
+        }
+        Some(threads)
+    }
+
+    fn selected_thread(&self) -> Option<&Thread> {
+        let process = &self.processes[self.process.selected()?];
+        Some(&process.threads[self.thread.selected()?])
+    }
+
+    /// Returns the logs merged in the log pane along with the processes and threads they are
+    /// from, in the order [`sort_logs`] gives, or `None` when it shows every log of the selected
+    /// thread alone.
+    ///
+    /// The logs of the selected thread are treated as merged while limited to a session, filtered,
+    /// limited to a target or limited by level, so their indices are positions among the logs
+    /// shown.
+    fn merged_logs(&self) -> Option<Vec<((Pid, Pthread), &Log)>> {
+        let key = self.merged_key()?;
+        let mut merged = self.merged.lock().unwrap();
+        if !merged.as_ref().is_some_and(|merged| merged.key == key) {
+            *merged = Some(Merged {
+                logs: self.merge_logs(&key),
+                key,
+            });
+        }
+        let merged = merged.as_ref().unwrap();
+        Some(
+            merged
+                .logs
+                .iter()
+                .map(|&(process, thread, position)| {
+                    let process = &self.processes[process];
+                    let thread = &process.threads[thread];
+                    ((process.id, thread.id), &thread.log[position])
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns what the logs of the log pane are merged from, or `None` when it shows every log of
+    /// the selected thread alone, see [`App::merged_logs`].
+    fn merged_key(&self) -> Option<MergedKey> {
+        // A session stopping changes the logs it contains.
+        let session = self
+            .session
+            .map(|session| (session, self.sessions[session].end));
+        let threads = if self.correlation.is_some() {
+            Vec::new()
+        } else if let Some(threads) = self.merged_threads() {
+            threads
+                .into_iter()
+                .map(|(pid, thread)| {
+                    let process = self.process_id_map[&pid];
+                    (process, self.processes[process].thread_id_map[&thread.id])
+                })
+                .collect()
+        } else if session.is_some()
+            || self.filter.is_some()
+            || self.target_filter.is_some()
+            || self.display_level != log::Level::Trace
+            || !self.target_levels.is_empty()
+        {
+            let thread = self.selected_thread()?;
+            let process = self.process.selected()?;
+            vec![(process, self.processes[process].thread_id_map[&thread.id])]
+        } else {
+            return None;
+        };
+        Some(MergedKey {
+            generation: self.generation,
+            correlation: self.correlation.clone(),
+            threads,
+            session,
+            filter: self.filter.clone(),
+            target_filter: self.target_filter.clone(),
+            display_level: self.display_level,
+            target_levels: self.target_levels.clone(),
+        })
+    }
+
+    /// Merges the logs described by `key`, returning the indices of their processes and threads
+    /// and their positions among the logs kept of their threads, sorted by time.
+    fn merge_logs(&self, key: &MergedKey) -> Vec<(usize, usize, usize)> {
+        let processes = &self.processes;
+        let thread_logs = move |(process, thread): (usize, usize)| {
+            processes[process].threads[thread]
+                .log
+                .iter()
+                .enumerate()
+                .map(move |(position, log)| ((process, thread, position), log))
+        };
+        let mut logs = if let Some(correlation) = &key.correlation {
+            (0..processes.len())
+                .flat_map(|process| {
+                    (0..processes[process].threads.len()).map(move |thread| (process, thread))
+                })
+                .flat_map(thread_logs)
+                .filter(|(_, log)| log.correlation.as_ref() == Some(correlation))
+                .collect::<Vec<_>>()
+        } else {
+            key.threads.iter().copied().flat_map(thread_logs).collect()
+        };
+        if let Some((session, _)) = key.session {
+            let session = &self.sessions[session];
+            logs.retain(|(_, log)| session.contains(log.time));
+        }
+        if let Some(filter) = &key.filter {
+            logs.retain(|(_, log)| log.message.to_lowercase().contains(filter.as_str()));
+        }
+        if let Some(target) = &key.target_filter {
+            logs.retain(|(_, log)| log.has_target(target));
+        }
+        logs.retain(|(_, log)| key.shows_level(log));
+        sort_logs(&mut logs, |&((process, _, _), log)| (process, log));
+        logs.into_iter().map(|(indices, _)| indices).collect()
+    }
+
+    /// Returns the indices of the first and last logs which can be shown in the log pane.
+    ///
+    /// Indices into a single thread are relative to every log received, see [`Thread::first`],
+    /// while indices into merged threads are positions in their logs sorted by time.
+    fn log_bounds(&self) -> Option<(usize, usize)> {
+        if let Some(logs) = self.merged_logs() {
+            Some((0, logs.len().saturating_sub(1)))
+        } else {
+            let thread = self.selected_thread()?;
+            Some((thread.first, thread.last()))
+        }
+    }
+
+    /// Scrolls the log pane so the latest log is in view at the bottom of its `height` rows.
+    fn follow_latest(&mut self, height: usize) {
+        self.clamp_selection();
+        if let Some((first, last)) = self.log_bounds() {
+            self.log = last.saturating_sub(height.saturating_sub(1)).max(first);
+        }
+    }
+
+    /// Returns the number of logs at each level within `scope`, indexed by `level as usize - 1`.
+    fn level_counts(&self, scope: CountScope) -> [usize; 5] {
+        let mut counts = [0; 5];
+        match scope {
+            CountScope::Global => {
+                for process in self.processes.iter().filter(|p| p.id != self.server) {
+                    for (count, process_count) in counts.iter_mut().zip(process.level_counts) {
+                        *count += process_count;
+                    }
+                }
+            }
+            CountScope::Thread => {
+                for log in self
+                    .selected_thread()
+                    .into_iter()
+                    .flat_map(|thread| &thread.log)
+                {
+                    counts[log.level as usize - 1] += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Returns the number of logs in the log pane within each of the last `buckets` intervals of
+    /// `histogram_bucket`, oldest first, where the last interval ends at the latest log.
+    fn histogram(&self, buckets: usize) -> Vec<u64> {
+        let mut counts = vec![0; buckets];
+        let logs = self.merged_logs().map_or_else(
+            || {
+                self.selected_thread()
+                    .into_iter()
+                    .flat_map(|thread| &thread.log)
+                    .collect()
+            },
+            |logs| logs.into_iter().map(|(_, log)| log).collect::<Vec<_>>(),
+        );
+
+        let Some(end) = logs.iter().map(|log| log.time).max() else {
+            return counts;
+        };
+        let bucket = self.histogram_bucket.as_nanos();
+        for log in logs {
+            let back = (end - log.time).as_nanos() / bucket;
+            if let Some(count) = usize::try_from(back)
+                .ok()
+                .and_then(|back| buckets.checked_sub(back + 1))
+                .and_then(|i| counts.get_mut(i))
+            {
+                *count += 1;
+            }
+        }
+        counts
+    }
+
+    /// Returns the logs of every process and thread whose messages contain the searched text,
+    /// in the order [`sort_logs`] gives, along with the indices of their processes and threads and
+    /// their positions among the logs kept of their threads.
+    fn search_results(&self) -> Vec<(usize, usize, usize, &Log)> {
+        let Some(search) = &self.search else {
+            return Vec::new();
+        };
+        let key = (search.clone(), self.generation);
+        let mut searched = self.searched.lock().unwrap();
+        if !searched
+            .as_ref()
+            .is_some_and(|searched| searched.key == key)
+        {
+            *searched = Some(Searched {
+                results: self.search_logs(search),
+                key,
+            });
+        }
+        let searched = searched.as_ref().unwrap();
+        searched
+            .results
+            .iter()
+            .map(|&(process, thread, position)| {
+                (
+                    process,
+                    thread,
+                    position,
+                    &self.processes[process].threads[thread].log[position],
+                )
+            })
+            .collect()
+    }
+
+    /// Searches every log for `search`, returning the indices of the processes and threads of the
+    /// logs found, and their positions among the logs kept of their threads, sorted as by
+    /// [`App::search_results`].
+    fn search_logs(&self, search: &str) -> Vec<(usize, usize, usize)> {
+        let mut results = self
+            .processes
+            .iter()
+            .enumerate()
+            .flat_map(|(p, process)| {
+                process
+                    .threads
+                    .iter()
+                    .enumerate()
+                    .flat_map(move |(t, thread)| {
+                        thread
+                            .log
+                            .iter()
+                            .enumerate()
+                            .map(move |(position, log)| (p, t, position, log))
+                    })
+            })
+            .filter(|(_, _, _, log)| log.message.to_lowercase().contains(search))
+            .collect::<Vec<_>>();
+        sort_logs(&mut results, |&(process, _, _, log)| (process, log));
+        results
+            .into_iter()
+            .map(|(process, thread, position, _)| (process, thread, position))
+            .collect()
+    }
+
+    pub fn next_search_result(&mut self, n: usize) {
+        let last = self.search_results().len().saturating_sub(1);
+        self.search_result = self.search_result.saturating_add(n).min(last);
+    }
+
+    pub fn previous_search_result(&mut self, n: usize) {
+        self.search_result = self.search_result.saturating_sub(n);
+    }
+
+    /// Shows the selected search result in the log pane, selecting its process and thread.
+    ///
+    /// Comparisons and correlations are left, since they would hide the thread. When the log is
+    /// hidden from the log pane, such as by a filter, it scrolls to the first log after it.
+    pub fn open_search_result(&mut self) {
+        let Some(&(process, thread, position, log)) = self.search_results().get(self.search_result)
+        else {
+            return;
+        };
+        let (target, time) = (std::ptr::from_ref(log), log.time);
+        self.view = View::Detail;
+        self.correlation = None;
+        self.compared_threads.clear();
+        self.select_process(process);
+        self.thread.select(Some(thread));
+        self.follow = false;
+        self.log = match self.merged_logs() {
+            Some(logs) => logs
+                .iter()
+                .position(|&(_, log)| std::ptr::eq(log, target))
+                .unwrap_or_else(|| logs.partition_point(|(_, log)| log.time < time)),
+            None => self.processes[process].threads[thread].first + position,
+        };
+        self.clamp_selection();
+    }
+
+    /// Returns the logs shown in the log pane, from the top of the pane onwards, along with their
+    /// indices and the processes and threads they were logged from.
+    fn visible_logs(&self) -> Vec<(usize, (Pid, Pthread), &Log)> {
+        if let Some(logs) = self.merged_logs() {
+            logs.into_iter()
+                .enumerate()
+                .skip(self.log)
+                .map(|(i, (thread, log))| (i, thread, log))
+                .collect()
+        } else if let (Some(process), Some(thread)) =
+            (self.process.selected(), self.selected_thread())
+        {
+            let pid = self.processes[process].id;
+            thread
+                .log
+                .iter()
+                .enumerate()
+                .skip(self.log - thread.first)
+                .map(|(i, log)| (thread.first + i, (pid, thread.id), log))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Marks or unmarks the selected thread for comparison, replacing any threads marked in
+    /// other processes.
+    pub fn toggle_compare_thread(&mut self) {
+        self.clamp_selection();
+        if let (Some(process), Some(thread)) = (self.process.selected(), self.thread.selected()) {
+            let process = &self.processes[process];
+            let key = (process.id, process.threads[thread].id);
+            if let Some(position) = self.compared_threads.iter().position(|&c| c == key) {
+                self.compared_threads.remove(position);
+            } else {
+                self.compared_threads.retain(|&(pid, _)| pid == key.0);
+                if self.compared_threads.len() == 2 {
+                    self.compared_threads.remove(0);
+                }
+                self.compared_threads.push(key);
+            }
+            self.scroll_to_top();
+        }
+    }
+
+    /// Scrolls the log pane to its first log, clamping the selection so it only refers to the
+    /// processes, threads and logs which exist.
+    ///
+    /// The first log of a thread whose oldest logs were removed is not at index `0`, see
+    /// [`Thread::first`].
+    fn scroll_to_top(&mut self) {
+        self.log = 0;
+        self.clamp_selection();
+    }
+
+    fn select_process(&mut self, process: usize) {
+        self.process.select(Some(process));
+        self.thread
+            .select(self.thread_order(process).first().copied());
+        self.tree_thread = false;
+        self.scroll_to_top();
+    }
+
+    pub fn next_overview(&mut self) {
+        self.clamp_selection();
+        let order = self.overview_order();
+        if !order.is_empty() {
+            let position = self
+                .process
+                .selected()
+                .and_then(|selected| order.iter().position(|&i| i == selected))
+                .map_or(0, |position| (position + 1) % order.len());
+            self.select_process(order[position]);
+        }
+    }
+
+    pub fn previous_overview(&mut self) {
+        self.clamp_selection();
+        let order = self.overview_order();
+        if !order.is_empty() {
+            let position = self
+                .process
+                .selected()
+                .and_then(|selected| order.iter().position(|&i| i == selected))
+                .map_or(0, |position| {
+                    position.checked_sub(1).unwrap_or(order.len() - 1)
+                });
+            self.select_process(order[position]);
+        }
+    }
+
+    /// Selects the process after the selected one, or the first process when none is selected.
+    ///
+    /// In the tree, selects the row after the selected one, whether a process or a thread.
+    pub fn next_process(&mut self) {
+        self.clamp_selection();
+        if self.tree {
+            self.step_tree(true);
+            return;
+        }
+        let order = self.process_order();
+        if !order.is_empty() {
+            let position = self
+                .process
+                .selected()
+                .and_then(|selected| order.iter().position(|&i| i == selected))
+                .map_or(0, |position| (position + 1) % order.len());
+            self.select_process(order[position]);
+        }
+    }
+
+    /// Selects the process before the selected one, or the first process when none is selected.
+    ///
+    /// In the tree, selects the row before the selected one, whether a process or a thread.
+    pub fn previous_process(&mut self) {
+        self.clamp_selection();
+        if self.tree {
+            self.step_tree(false);
+            return;
+        }
+        let order = self.process_order();
+        if !order.is_empty() {
+            let position = self
+                .process
+                .selected()
+                .and_then(|selected| order.iter().position(|&i| i == selected))
+                .map_or(0, |position| {
+                    position.checked_sub(1).unwrap_or(order.len() - 1)
+                });
+            self.select_process(order[position]);
+        }
+    }
+
+    /// Selects the thread after the selected one, or the first thread when none is selected.
+    pub fn next_thread(&mut self) {
+        self.clamp_selection();
+        let Some(process) = self.process.selected() else {
+            return;
+        };
+        let order = self.thread_order(process);
+        if !order.is_empty() {
+            let position = self
+                .thread
+                .selected()
+                .and_then(|selected| order.iter().position(|&i| i == selected))
+                .map_or(0, |position| (position + 1) % order.len());
+            self.select_thread(process, order[position]);
+        }
+    }
+
+    /// Selects the thread before the selected one, or the first thread when none is selected.
+    pub fn previous_thread(&mut self) {
+        self.clamp_selection();
+        let Some(process) = self.process.selected() else {
+            return;
+        };
+        let order = self.thread_order(process);
+        if !order.is_empty() {
+            let position = self
+                .thread
+                .selected()
+                .and_then(|selected| order.iter().position(|&i| i == selected))
+                .map_or(0, |position| {
+                    position.checked_sub(1).unwrap_or(order.len() - 1)
+                });
+            self.select_thread(process, order[position]);
+        }
+    }
+
+    pub fn next_log(&mut self, n: usize) {
+        self.clamp_selection();
+        if let Some((_, last)) = self.log_bounds() {
+            self.log = std::cmp::min(self.log + n, last);
+        }
+    }
+
+    /// Returns the number of logs shown at once in the log pane, or of results in the search view,
+    /// as last drawn.
+    fn page_size(&self) -> usize {
+        // The borders and the header take 3 rows.
+        usize::from(self.log_pane.height.saturating_sub(3)).max(1)
+    }
+
+    /// Copies the top log of the log pane, as a line in the format of the log file, to the
+    /// clipboard, or to a file when there is none, returning the message to show.
+    pub fn copy_top_log(&mut self) -> String {
+        self.clamp_selection();
+        let line = self.visible_logs().first().map(|&(_, (pid, thread), log)| {
+            let mut line = Vec::new();
+            // Writing to a `Vec` cannot fail.
+            let _ = export::write_line(&mut line, pid, thread, log);
+            String::from_utf8_lossy(&line).trim_end().to_string()
+        });
+        match line.map(|line| clipboard::copy(&line)) {
+            None => String::from("There is no log to copy"),
+            Some(Ok(clipboard::Copied::Clipboard)) => String::from("Copied the top log"),
+            Some(Ok(clipboard::Copied::File(path))) => {
+                format!(
+                    "There is no clipboard, wrote the top log to {}",
+                    path.display()
+                )
+            }
+            Some(Err(err)) => format!("Failed to copy the top log: {err}"),
+        }
+    }
+
+    /// Scrolls the log pane down by the number of logs it shows.
+    pub fn page_down(&mut self) {
+        self.next_log(self.page_size());
+    }
+
+    /// Scrolls the log pane up by the number of logs it shows.
+    pub fn page_up(&mut self) {
+        self.previous_log(self.page_size());
+    }
+
+    pub fn previous_log(&mut self, n: usize) {
+        self.follow = false;
+        self.clamp_selection();
+        if let Some((first, _)) = self.log_bounds() {
+            self.log = std::cmp::max(self.log.saturating_sub(n), first);
+        }
+    }
+
+    /// Selects the process, thread or log clicked on, or scrolls the log pane with the wheel.
+    ///
+    /// Clicks outside of any row, and every mouse event while text is being entered, are ignored.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if self.input.is_some() || self.view != View::Detail {
+            return;
+        }
+        let (column, row) = (mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let target = self
+                    .targets
+                    .iter()
+                    .find(|(area, _)| contains(*area, column, row))
+                    .map(|&(_, target)| target);
+                // The rows were drawn before the click, since when processes may have been
+                // removed.
+                self.clamp_selection();
+                match target {
+                    Some(Target::Process(process)) if process < self.processes.len() => {
+                        if self.process.selected() != Some(process) {
+                            self.select_process(process);
+                        }
+                        self.tree_thread = false;
+                    }
+                    Some(Target::Thread(process, thread))
+                        if self
+                            .processes
+                            .get(process)
+                            .is_some_and(|process| thread < process.threads.len()) =>
+                    {
+                        self.select_thread(process, thread);
+                    }
+                    Some(Target::Log(log)) => {
+                        self.follow = false;
+                        self.log = log;
+                        self.clamp_selection();
+                    }
+                    _ => {}
+                }
+            }
+            MouseEventKind::ScrollDown if contains(self.log_pane, column, row) => self.next_log(1),
+            MouseEventKind::ScrollUp if contains(self.log_pane, column, row) => {
+                self.previous_log(1);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let log_file = args
+        .log_file
+        .clone()
+        .map(|path| LogFile::open(path, args.log_file_max_size, args.log_file_backups))
+        .transpose()?;
+
+    // The sockets are listening before the terminal is set up, so they never exist without
+    // accepting connections.
+    let listen_fd = args.listen_fd.or_else(activation_fd);
+    // The sockets bound by the server, removed when it exits. An inherited socket belongs to the
+    // supervisor which may pass it on to the next server.
+    let mut sockets = Vec::new();
+    let mut listeners = Vec::new();
+    let mut tcp_listeners = Vec::new();
+    if let Some(fd) = listen_fd {
+        // SAFETY: The descriptor is inherited for the server to use and nothing else takes
+        // ownership of it.
+        let listener = unsafe { TcpListener::from_raw_fd(fd) };
+        // Only sockets of the Internet address families have an address as a TCP listener, any
+        // other socket is taken to be a Unix socket.
+        if listener.local_addr().is_ok() {
+            tcp_listeners.push(listener);
+        } else {
+            listeners.push(UnixListener::from(OwnedFd::from(listener)));
+        }
+    } else {
+        for socket in &args.sockets {
+            match bind(socket) {
+                Ok(listener) => {
+                    listeners.push(listener);
+                    sockets.push(socket.clone());
+                }
+                Err(err) => {
+                    eprintln!("Failed to listen on {socket}: {err}");
+                    remove_sockets(&sockets);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+    for address in &args.tcp_addresses {
+        match TcpListener::bind(address) {
+            Ok(listener) => tcp_listeners.push(listener),
+            Err(err) => {
+                eprintln!("Failed to listen on {address}: {err}");
+                remove_sockets(&sockets);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // setup terminal, left alone with `--no-tui` so logs can be printed to it
+    let mut terminal = None;
+    let mut alternate_screen = true;
+    if !args.no_tui {
+        alternate_screen =
+            has_alternate_screen(std::env::var("TERM").ok().as_deref(), args.no_alt_screen);
+        match setup_terminal(alternate_screen) {
+            Ok(setup) => terminal = Some(setup),
+            Err(err) => {
+                eprintln!("Failed to set up the terminal: {err}");
+                remove_sockets(&sockets);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // create app and run it
+    let quiet = args.quiet;
+    let color = args.color.enabled();
+    let start = Instant::now();
+    let mut app = App::new();
+    app.keep_raw = args.keep_raw;
+    app.max_frame_length = args.max_frame_length;
+    app.nul_policy = args.nul;
+    app.ansi = args.ansi;
+    app.dedup = args.dedup;
+    app.system_log = args.system_log;
+    app.palette = args.palette.resolve(args.color);
+    app.trigger = args.on_match.clone().map(|rule| {
+        Trigger::new(
+            rule,
+            args.command.clone(),
+            Duration::from_secs(args.on_match_interval),
+        )
+    });
+    app.log_file = log_file;
+    app.flapping =
+        flapping::Detector::new(Duration::from_secs(args.flap_window), args.flap_threshold);
+    app.highlights = args
+        .highlights
+        .iter()
+        .filter(|keyword| !keyword.is_empty())
+        .map(|keyword| keyword.to_ascii_lowercase())
+        .collect();
+    app.shown_keys = args.shown_keys.iter().cloned().collect();
+    app.hidden_keys = args.hidden_keys.iter().cloned().collect();
+    app.histogram_bucket = Duration::from_millis(args.histogram_bucket.max(1));
+    app.max_processes = args.max_processes;
+    app.process_limit_policy = args.on_max_processes;
+    app.max_logs_per_thread = args.max_logs_per_thread.max(1);
+    app.time_format = args.time_format;
+    app.utc = args.utc;
+    app.print_logs = args.no_tui;
+    if !alternate_screen && !args.no_alt_screen {
+        app.message = Some(String::from(
+            "The terminal has no alternate screen, drawing over the main screen instead",
+        ));
+    }
+    let app = Arc::new(RwLock::new(app));
+    let res = match &mut terminal {
+        Some(terminal) => run_app(terminal, app.clone(), listeners, tcp_listeners, args),
+        None => run_headless(&app, listeners, tcp_listeners, &args),
+    };
+
+    // restore terminal
+    if let Some(terminal) = &mut terminal {
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+        if alternate_screen {
+            execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        } else {
+            terminal.clear()?;
+        }
+        terminal.show_cursor()?;
+    }
+
+    if let Some(log_file) = &mut app.write().unwrap().log_file {
+        log_file.flush()?;
+    }
+
+    remove_sockets(&sockets);
+
+    if let Err(err) = res {
+        println!("{err:?}");
+    }
+
+    if !quiet {
+        print!("{}", summary(&app.read().unwrap(), start.elapsed(), color));
+    }
+
+    Ok(())
+}
+
+/// Sets up the terminal to draw the interface, on its alternate screen when `alternate_screen`.
+///
+/// When a step fails the steps before it are undone, so the shell is left usable.
+fn setup_terminal(
+    alternate_screen: bool,
+) -> std::io::Result<Terminal<CrosstermBackend<std::io::Stdout>>> {
+    let setup = || -> std::io::Result<_> {
+        let mut log = std::io::stdout();
+        if alternate_screen {
+            execute!(log, EnterAlternateScreen)?;
+        }
+        enable_raw_mode()?;
+        execute!(log, EnableMouseCapture)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(log))?;
+        if !alternate_screen {
+            terminal.clear()?;
+        }
+        Ok(terminal)
+    };
+    setup().map_err(|err| {
+        // Undoing a step which was never taken is harmless, and failures are ignored as the
+        // original error is the one reported.
+        let mut log = std::io::stdout();
+        let _ = disable_raw_mode();
+        let _ = execute!(log, DisableMouseCapture);
+        if alternate_screen {
+            let _ = execute!(log, LeaveAlternateScreen);
+        }
+        err
+    })
+}
+
+/// Returns whether the interface is drawn on the alternate screen of the terminal `term`, named as
+/// in `TERM`, rather than over its main screen, as with `--no-alt-screen`.
+///
+/// Entering the alternate screen only writes an escape sequence, which terminals lacking one
+/// ignore, so minimal terminals are recognized by name.
+fn has_alternate_screen(term: Option<&str>, no_alt_screen: bool) -> bool {
+    !no_alt_screen && term.is_some_and(|term| !term.is_empty() && term != "dumb")
+}
+
+/// Returns a summary of the capture, a single line totalling all processes followed by a line for
+/// each process, with error and warning counts colored when `color` is set.
+fn summary(app: &App, duration: Duration, color: bool) -> String {
+    let errors_text = |errors: usize| {
+        let text = format!("{errors} errors");
+        if color {
+            text.red().to_string()
+        } else {
+            text
+        }
+    };
+    let warnings_text = |warnings: usize| {
+        let text = format!("{warnings} warnings");
+        if color {
+            text.yellow().to_string()
+        } else {
+            text
+        }
+    };
+
+    let total = app.processes.iter().map(Process::total).sum::<usize>();
+    let errors = app
+        .processes
+        .iter()
+        .map(|process| process.count(log::Level::Error))
+        .sum::<usize>();
+    let warnings = app
+        .processes
+        .iter()
+        .map(|process| process.count(log::Level::Warn))
+        .sum::<usize>();
+
+    let mut summary = format!(
+        "{total} logs from {} processes ({}, {}) over {}\n",
+        app.processes.len(),
+        errors_text(errors),
+        warnings_text(warnings),
+        format_duration(duration)
+    );
+    for process in &app.processes {
+        summary.push_str(&format!(
+            "  {}: {} logs ({}, {})\n",
+            app.process_full_label(process),
+            process.total(),
+            errors_text(process.count(log::Level::Error)),
+            warnings_text(process.count(log::Level::Warn))
+        ));
+    }
+    summary
+}
+
+/// Formats `duration` to the second, e.g. `1h 2m 3s`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m {s}s"),
+        (h, m, s) => format!("{h}h {m}m {s}s"),
+    }
+}
+
+/// Binds a listener to `socket`, replacing a stale socket left by a server which exited without
+/// removing it.
+///
+/// # Errors
+///
+/// When another server is listening on `socket` ([`std::io::ErrorKind::AddrInUse`]), or failing
+/// to bind.
+fn bind(socket: &str) -> std::io::Result<UnixListener> {
+    match UnixListener::bind(socket) {
+        Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => {
+            // A live server accepts connections, a stale socket refuses them.
+            if UnixStream::connect(socket).is_ok() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AddrInUse,
+                    "another server is listening on it",
+                ));
+            }
+            std::fs::remove_file(socket)?;
+            UnixListener::bind(socket)
+        }
+        listener => listener,
+    }
+}
+
+/// Removes the Unix sockets the server bound, reporting those which cannot be removed rather than
+/// stopping at them.
+fn remove_sockets(sockets: &[String]) {
+    for socket in sockets {
+        if let Err(err) = std::fs::remove_file(socket) {
+            eprintln!("Failed to remove {socket}: {err}");
+        }
+    }
+}
+
+/// Returns the listening socket passed by systemd socket activation, if any.
+fn activation_fd() -> Option<RawFd> {
+    let pid = std::env::var("LISTEN_PID").ok()?.parse::<u32>().ok()?;
+    let fds = std::env::var("LISTEN_FDS").ok()?.parse::<u32>().ok()?;
+    (pid == std::process::id() && fds >= 1).then_some(SD_LISTEN_FDS_START)
+}
+
+/// Starts the threads receiving logs from `listeners` and `tcp_listeners`, writing snapshots on
+/// `SIGUSR1`, asking the server to exit on other signals and doing periodic upkeep.
+fn spawn_threads(
+    app: &Arc<RwLock<App>>,
+    listeners: Vec<UnixListener>,
+    tcp_listeners: Vec<TcpListener>,
+    args: &Args,
+) -> std::io::Result<()> {
+    let Args {
+        export_format,
+        wire_format,
+        prune_after,
+        ..
+    } = *args;
+
+    // Snapshots are written from this thread rather than from within the signal handler, keeping
+    // the handler itself async-signal-safe.
+    let mut signals = Signals::new([SIGUSR1])?;
+    let app_clone = app.clone();
+    let _ = std::thread::spawn(move || {
+        for _ in signals.forever() {
+            // There is nowhere to report a failure while the TUI owns the terminal.
+            let _ = export::export(&app_clone.read().unwrap(), export_format);
+        }
+    });
+
+    // Exits through the same path as `q`, so the terminal is restored and the sockets removed
+    // once, by `main`.
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    let app_clone = app.clone();
+    let _ = std::thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            app_clone.write().unwrap().quit = true;
+        }
+    });
+
+    if app.read().unwrap().log_file.is_some() {
+        let app_clone = app.clone();
+        let _ = std::thread::spawn(move || loop {
+            std::thread::sleep(LogFile::FLUSH_INTERVAL);
+            let mut app = app_clone.write().unwrap();
+            if let Some(Err(err)) = app.log_file.as_mut().map(LogFile::flush) {
+                app.message = Some(format!("Failed to write to the log file: {err}"));
+            }
+        });
+    }
+
+    if let Some(prune_after) = prune_after {
+        let app_clone = app.clone();
+        let grace = Duration::from_secs(prune_after);
+        let _ = std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_secs(1));
+            app_clone.write().unwrap().prune_disconnected(grace);
+        });
+    }
+
+    // Every listener feeds the same app, connection ids stay unique through `NEXT_CONNECTION`.
+    for listener in listeners {
+        let app_clone = app.clone();
+        let _ = std::thread::spawn(move || accept(listener.incoming(), app_clone, wire_format));
+    }
+    for listener in tcp_listeners {
+        let app_clone = app.clone();
+        let _ = std::thread::spawn(move || accept(listener.incoming(), app_clone, wire_format));
+    }
+    Ok(())
+}
+
+/// Receives logs without drawing the interface, with `--no-tui`, until a signal asks the server
+/// to exit.
+fn run_headless(
+    app: &Arc<RwLock<App>>,
+    listeners: Vec<UnixListener>,
+    tcp_listeners: Vec<TcpListener>,
+    args: &Args,
+) -> std::io::Result<()> {
+    spawn_threads(app, listeners, tcp_listeners, args)?;
+    while !app.read().unwrap().quit {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok(())
+}
+
+fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: Arc<RwLock<App>>,
+    listeners: Vec<UnixListener>,
+    tcp_listeners: Vec<TcpListener>,
+    args: Args,
+) -> std::io::Result<()> {
+    spawn_threads(&app, listeners, tcp_listeners, &args)?;
+    let Args {
+        frame_rate,
+        keys,
+        export_format,
+        ..
+    } = args;
+    let frame = Duration::from_secs(1) / frame_rate;
+
+    loop {
+        if app.read().unwrap().quit {
+            break;
+        }
+        let app_clone = app.clone();
+        terminal.draw(|f| ui(f, app_clone))?;
+
+        // Redraws once a frame even without input, drawing only what changed since the last.
+        if !event::poll(frame)? {
+            continue;
+        }
+        let event = event::read()?;
+        if let Event::Mouse(mouse) = event {
+            app.write().unwrap().handle_mouse(mouse);
+            continue;
+        }
+        if let Event::Key(key) = event {
+            // Raw mode delivers Ctrl-C as a key press rather than `SIGINT`.
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                break;
+            }
+            {
+                let mut guard = app.write().unwrap();
+                guard.message = None;
+                if guard.input.is_some() {
+                    guard.handle_input(key.code);
+                    if guard.quit {
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            let view = app.read().unwrap().view;
+            if view == View::Overview {
+                match keys.action(key.code) {
+                    Some(Action::Quit) => break,
+                    Some(Action::PreviousProcess) => app.write().unwrap().previous_overview(),
+                    Some(Action::NextProcess) => app.write().unwrap().next_overview(),
+                    // The log pane is not shown.
+                    Some(_) => {}
+                    None => match key.code {
+                        KeyCode::Char(':') => app.write().unwrap().start_command(),
+                        KeyCode::Char('o') => app.write().unwrap().view = View::Detail,
+                        KeyCode::Char('p') => app.write().unwrap().toggle_pin_process(),
+                        KeyCode::Char('a') => app.write().unwrap().start_alias(),
+                        KeyCode::Char('c') => {
+                            let mut guard = app.write().unwrap();
+                            guard.overview_sort = guard.overview_sort.next();
+                        }
+                        KeyCode::Enter => {
+                            let mut guard = app.write().unwrap();
+                            if let Some(process) = guard.process.selected() {
+                                guard.select_process(process);
+                                guard.view = View::Detail;
+                            }
+                        }
+                        _ => {}
+                    },
+                }
+                continue;
+            }
+
+            if view == View::Search {
+                let mut guard = app.write().unwrap();
+                match keys.action(key.code) {
+                    Some(Action::Quit) => break,
+                    Some(Action::PreviousLog(n)) => guard.previous_search_result(n),
+                    Some(Action::NextLog(n)) => guard.next_search_result(n),
+                    Some(Action::PreviousPage) => {
+                        let page = guard.page_size();
+                        guard.previous_search_result(page);
+                    }
+                    Some(Action::NextPage) => {
+                        let page = guard.page_size();
+                        guard.next_search_result(page);
+                    }
+                    Some(Action::Top) => guard.search_result = 0,
+                    Some(Action::Bottom) => guard.next_search_result(usize::MAX),
+                    // Processes and threads are not listed.
+                    Some(_) => {}
+                    None => match key.code {
+                        KeyCode::Char(':') => guard.start_command(),
+                        KeyCode::Char('?') => guard.start_search(),
+                        KeyCode::Enter => guard.open_search_result(),
+                        KeyCode::Esc => guard.set_search(""),
+                        _ => {}
+                    },
+                }
+                continue;
+            }
+
+            // The target panel takes the keys moving through logs and changing levels while shown.
+            if app.read().unwrap().target_panel.is_some() {
+                let mut guard = app.write().unwrap();
+                let handled = match (keys.action(key.code), key.code) {
+                    (Some(Action::PreviousLog(n)), _) => {
+                        guard.previous_target(n);
+                        true
+                    }
+                    (Some(Action::NextLog(n)), _) => {
+                        guard.next_target(n);
+                        true
+                    }
+                    (None, KeyCode::Char('+')) => {
+                        guard.shift_target_level(true);
+                        true
+                    }
+                    (None, KeyCode::Char('-')) => {
+                        guard.shift_target_level(false);
+                        true
+                    }
+                    (None, KeyCode::Backspace) => {
+                        guard.clear_target_level();
+                        true
+                    }
+                    (None, KeyCode::Esc) => {
+                        guard.target_panel = None;
+                        true
+                    }
+                    _ => false,
+                };
+                if handled {
+                    continue;
+                }
+            }
+
+            if let Some(action) = keys.action(key.code) {
+                let mut guard = app.write().unwrap();
+                match action {
+                    Action::Quit => break,
+                    Action::PreviousProcess => guard.previous_process(),
+                    Action::NextProcess => guard.next_process(),
+                    Action::PreviousThread => guard.previous_thread(),
+                    Action::NextThread => guard.next_thread(),
+                    Action::PreviousLog(n) => guard.previous_log(n),
+                    Action::NextLog(n) => guard.next_log(n),
+                    Action::PreviousPage => guard.page_up(),
+                    Action::NextPage => guard.page_down(),
+                    Action::Top => {
+                        guard.scroll_to_top();
+                        guard.follow = false;
+                    }
+                    Action::Bottom => {
+                        guard.clamp_selection();
+                        if let Some((_, last)) = guard.log_bounds() {
+                            guard.log = last;
+                        }
+                    }
+                }
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char(':') => app.write().unwrap().start_command(),
+                KeyCode::Char('o') => app.write().unwrap().view = View::Overview,
+                KeyCode::Char('p') => app.write().unwrap().toggle_pin_process(),
+                KeyCode::Char('P') => app.write().unwrap().toggle_pin_thread(),
+                KeyCode::Char('a') => app.write().unwrap().start_alias(),
+                KeyCode::Char('b') => {
+                    let mut guard = app.write().unwrap();
+                    guard.show_raw = !guard.show_raw;
+                }
+                KeyCode::Char('k') => {
+                    let mut guard = app.write().unwrap();
+                    guard.expand_key_values = !guard.expand_key_values;
+                }
+                KeyCode::Char('F') => {
+                    let mut guard = app.write().unwrap();
+                    guard.follow = !guard.follow;
+                }
+                KeyCode::Char('v') => {
+                    let mut guard = app.write().unwrap();
+                    guard.show_histogram = !guard.show_histogram;
+                }
+                KeyCode::Char('i') => {
+                    let mut guard = app.write().unwrap();
+                    guard.counts = match guard.counts {
+                        Some(_) => None,
+                        None => Some(CountScope::Global),
+                    };
+                }
+                KeyCode::Char('I') => {
+                    let mut guard = app.write().unwrap();
+                    guard.counts = match guard.counts {
+                        Some(CountScope::Global) => Some(CountScope::Thread),
+                        Some(CountScope::Thread) => Some(CountScope::Global),
+                        None => None,
+                    };
+                }
+                KeyCode::Char('[') => {
+                    let mut guard = app.write().unwrap();
+                    guard.histogram_bucket =
+                        std::cmp::max(guard.histogram_bucket / 2, Duration::from_millis(1));
+                }
+                KeyCode::Char(']') => {
+                    let mut guard = app.write().unwrap();
+                    if let Some(bucket) = guard.histogram_bucket.checked_mul(2) {
+                        guard.histogram_bucket = bucket;
+                    }
+                }
+                KeyCode::Char('Y') => {
+                    let mut guard = app.write().unwrap();
+                    guard.message = Some(guard.copy_top_log());
+                }
+                KeyCode::Char('E') => {
+                    let mut guard = app.write().unwrap();
+                    guard.message =
+                        Some(match export::report(&guard, log::Level::Warn, None, None) {
+                            Ok(path) => format!("Exported to {}", path.display()),
+                            Err(err) => format!("Failed to export: {err}"),
+                        });
+                }
+                KeyCode::Char('S') => {
+                    let mut guard = app.write().unwrap();
+                    guard.message = Some(match export::export(&guard, export_format) {
+                        Ok(path) => format!("Exported to {}", path.display()),
+                        Err(err) => format!("Failed to export: {err}"),
+                    });
+                }
+                KeyCode::Char('M') => app.write().unwrap().start_marker(),
+                KeyCode::Char('/') => app.write().unwrap().start_filter(),
+                KeyCode::Char('?') => app.write().unwrap().start_search(),
+                KeyCode::Char('V') => app.write().unwrap().toggle_target_panel(),
+                KeyCode::Char('+') => app.write().unwrap().shift_display_level(true),
+                KeyCode::Char('-') => app.write().unwrap().shift_display_level(false),
+                KeyCode::Esc => {
+                    let mut guard = app.write().unwrap();
+                    if guard.filter.is_some() {
+                        guard.set_filter("");
+                    }
+                }
+                KeyCode::Char('x') => app.write().unwrap().toggle_compare_thread(),
+                KeyCode::Char('X') => {
+                    let mut guard = app.write().unwrap();
+                    guard.compared_threads.clear();
+                    guard.scroll_to_top();
+                }
+                KeyCode::Char('m') => {
+                    let mut guard = app.write().unwrap();
+                    guard.by_process = !guard.by_process;
+                    guard.scroll_to_top();
+                }
+                KeyCode::Char('Z') => {
+                    let mut guard = app.write().unwrap();
+                    guard.tree = !guard.tree;
+                }
+                KeyCode::Char('z') => app.write().unwrap().toggle_expanded(),
+                KeyCode::Char('T') => {
+                    let mut guard = app.write().unwrap();
+                    guard.timeline = !guard.timeline;
+                    guard.scroll_to_top();
+                }
+                KeyCode::Char('l') => {
+                    let mut guard = app.write().unwrap();
+                    guard.group_by_level = !guard.group_by_level;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn ui<B: Backend>(f: &mut Frame<B>, app: Arc<RwLock<App>>) {
+    let mut app = app.write().unwrap();
+    app.clamp_selection();
+
+    let size = f.size();
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+        .split(size);
+
+    let block = Block::default().style(Style::default());
+    f.render_widget(block, size);
+    app.targets.clear();
+    app.log_pane = Rect::default();
+
+    match app.view {
+        View::Detail => detail(f, &mut app, rows[0]),
+        View::Overview => overview(f, &app, rows[0]),
+        View::Search => {
+            app.log_pane = rows[0];
+            search(f, &app, rows[0]);
+        }
+    }
+    status(f, &app, rows[1]);
+
+    f.render_widget(app.palette, size);
+}
+
+fn status<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    if let Some(input) = &app.input {
+        let mut prompt = vec![Span::raw(format!(
+            "{}: {}█",
+            input.prompt.title(),
+            input.text
+        ))];
+        if input.prompt == Prompt::Command {
+            let completions = command::completions(&input.text)
+                .into_iter()
+                .map(|command| format!("{} {}", command.name, command.usage))
+                .collect::<Vec<_>>();
+            prompt.push(Span::styled(
+                format!("  {}", completions.join(" | ")),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        f.render_widget(Paragraph::new(Spans::from(prompt)), area);
+        return;
+    }
+
+    let mut status = vec![Span::raw(format!("Memory: {}", format_bytes(app.memory)))];
+    if let (View::Detail, Some(process)) = (app.view, app.process.selected()) {
+        let process = &app.processes[process];
+        status.push(Span::raw(format!(
+            " | {} up {}",
+            app.process_full_label(process),
+            format_duration(process.uptime())
+        )));
+        if let Some(context) = app.process_contexts.get(&process.id) {
+            let context = context
+                .iter()
+                .map(|(key, value)| format!("{}={}", sanitize(key), sanitize(value)))
+                .collect::<Vec<_>>()
+                .join(" ");
+            status.push(Span::raw(format!(" | {context}")));
+        }
+        if process.dropped != 0 {
+            status.push(Span::styled(
+                format!(" | Dropped: {}", process.dropped),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        let sampled = log::Level::iter()
+            .zip(process.sampling)
+            .filter(|&(_, rate)| rate > 1)
+            .map(|(level, rate)| format!("{level} 1/{rate}"))
+            .collect::<Vec<_>>();
+        if !sampled.is_empty() {
+            status.push(Span::raw(format!(" | Sampled: {}", sampled.join(", "))));
+        }
+    }
+    if let Some(message) = &app.message {
+        status.push(Span::raw(format!(" | {message}")));
+    }
+    if app.processes.iter().any(|process| {
+        process
+            .last_drop
+            .is_some_and(|t| t.elapsed() < DROP_WARNING)
+    }) {
+        let dropped = app
+            .processes
+            .iter()
+            .map(|process| process.dropped)
+            .sum::<u64>();
+        status.push(Span::styled(
+            format!(" | Dropping records ({dropped} dropped)"),
+            Style::default()
+                .fg(Color::Red)
+                .add_modifier(Modifier::BOLD | Modifier::SLOW_BLINK),
+        ));
+    }
+    for (lineage, connects) in app.flapping.flapping() {
+        status.push(Span::styled(
+            format!(" | Flapping: {connects} recent connections from lineage {lineage}"),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    f.render_widget(Paragraph::new(Spans::from(status)), area);
+}
+
+fn detail<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let constraints = if app.tree {
+        vec![Constraint::Length(17 + PREVIEW_WIDTH), Constraint::Min(0)]
+    } else if app.by_process {
+        vec![Constraint::Length(9), Constraint::Min(0)]
+    } else {
+        vec![
+            Constraint::Length(9),
+            Constraint::Length(15 + PREVIEW_WIDTH),
+            Constraint::Min(0),
+        ]
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(constraints)
+        .split(area);
+    let log_area = chunks[chunks.len() - 1];
+
+    // Tree
+    // ---------------------------------------------------------------------------------------------
+    if app.tree {
+        let rows = app.tree_rows();
+        let items = rows
+            .iter()
+            .map(|&(process, thread)| {
+                let process = &app.processes[process];
+                ListItem::new(match thread {
+                    Some(thread) => format!("  {}", thread_item(app, process, thread)),
+                    None => format!(
+                        "{} {}{}",
+                        if app.expanded.contains(&process.id) {
+                            '▾'
+                        } else {
+                            '▸'
+                        },
+                        app.process_marker(process),
+                        app.process_label(process)
+                    ),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let tree = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Process"))
+            .highlight_style(
+                Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .fg(Color::Cyan),
+            );
+
+        let mut tree_state = ListState::default();
+        tree_state.select(
+            app.tree_row()
+                .and_then(|selected| rows.iter().position(|&row| row == selected)),
+        );
+        app.targets
+            .extend(list_rows(chunks[0], tree_state.selected(), rows.len()).map(
+                |(area, position)| {
+                    let target = match rows[position] {
+                        (process, None) => Target::Process(process),
+                        (process, Some(thread)) => Target::Thread(process, thread),
+                    };
+                    (area, target)
+                },
+            ));
+        f.render_stateful_widget(tree, chunks[0], &mut tree_state);
+    } else {
+        // Process
+        // -----------------------------------------------------------------------------------------
+        let process_order = app.process_order();
+        let process_numbers = process_order
+            .iter()
+            .map(|&i| {
+                let process = &app.processes[i];
+                ListItem::new(format!(
+                    "{}{}",
+                    app.process_marker(process),
+                    app.process_label(process)
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let process_tabs = List::new(process_numbers)
+            .block(Block::default().borders(Borders::ALL).title("Process"))
+            .highlight_style(
+                Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .fg(Color::Cyan),
+            );
+
+        let mut process_state = ListState::default();
+        process_state.select(
+            app.process
+                .selected()
+                .and_then(|selected| process_order.iter().position(|&i| i == selected)),
+        );
+        app.targets.extend(
+            list_rows(chunks[0], process_state.selected(), process_order.len())
+                .map(|(area, position)| (area, Target::Process(process_order[position]))),
+        );
+        f.render_stateful_widget(process_tabs, chunks[0], &mut process_state);
+    }
+
+    // Thread
+    // ---------------------------------------------------------------------------------------------
+    if !app.by_process && !app.tree {
+        let selected = app.process.selected();
+        let thread_order = selected.map_or_else(Vec::new, |process| app.thread_order(process));
+        let thread_ids = selected.map_or_else(Vec::new, |process| {
+            let process = &app.processes[process];
+            thread_order
+                .iter()
+                .map(|&i| ListItem::new(thread_item(app, process, i)))
+                .collect()
+        });
+
+        let thread_tabs = List::new(thread_ids)
+            .block(Block::default().borders(Borders::ALL).title("Thread"))
+            .highlight_style(
+                Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .fg(Color::Cyan),
+            );
+
+        let mut thread_state = ListState::default();
+        thread_state.select(
+            app.thread
+                .selected()
+                .and_then(|selected| thread_order.iter().position(|&i| i == selected)),
+        );
+        app.targets.extend(
+            list_rows(chunks[1], thread_state.selected(), thread_order.len()).filter_map(
+                |(area, position)| Some((area, Target::Thread(selected?, thread_order[position]))),
+            ),
+        );
+        f.render_stateful_widget(thread_tabs, chunks[1], &mut thread_state);
+    }
+
+    // Histogram
+    // ---------------------------------------------------------------------------------------------
+    let log_area = if app.show_histogram {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(6), Constraint::Min(0)])
+            .split(log_area);
+        let counts = app.histogram(usize::from(rows[0].width.saturating_sub(2)));
+        let histogram = Sparkline::default()
+            .block(
+                Block::default()
+                    .title(format!(
+                        "Volume ({} ms per bar)",
+                        app.histogram_bucket.as_millis()
+                    ))
+                    .borders(Borders::ALL),
+            )
+            .data(&counts)
+            .style(Style::default().fg(Color::Cyan));
+        f.render_widget(histogram, rows[0]);
+        rows[1]
+    } else {
+        log_area
+    };
+
+    // Counts
+    // ---------------------------------------------------------------------------------------------
+    let log_area = if let Some(scope) = app.counts {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(log_area);
+        let counts = log::Level::iter()
+            .zip(app.level_counts(scope))
+            .flat_map(|(level, count)| {
+                [
+                    Span::styled(format!("{level} {count}"), Severity::from(level).style()),
+                    Span::raw("  "),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let title = match scope {
+            CountScope::Global => "Counts (all processes)",
+            CountScope::Thread => "Counts (selected thread)",
+        };
+        let counts = Paragraph::new(Spans::from(counts))
+            .block(Block::default().title(title).borders(Borders::ALL));
+        f.render_widget(counts, rows[0]);
+        rows[1]
+    } else {
+        log_area
+    };
+
+    // Target levels
+    // ---------------------------------------------------------------------------------------------
+    let log_area = if let Some(selected) = app.target_panel {
+        // At most 8 targets are listed at once, between the borders.
+        let height = u16::try_from(app.seen_targets.len().clamp(1, 8)).unwrap_or(8) + 2;
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(height), Constraint::Min(0)])
+            .split(log_area);
+        let items = app
+            .seen_targets
+            .iter()
+            .map(|target| {
+                let level = match app.target_levels.get(target) {
+                    Some(&level) => {
+                        Span::styled(format!("{level:<5}"), Severity::from(level).style())
+                    }
+                    None => Span::styled("-    ", Style::default().add_modifier(Modifier::DIM)),
+                };
+                ListItem::new(Spans::from(vec![level, Span::raw(format!(" {target}"))]))
+            })
+            .collect::<Vec<_>>();
+        let panel = List::new(items)
+            .block(
+                Block::default()
+                    .title("Target levels (+/- to change, Backspace to reset)")
+                    .borders(Borders::ALL),
+            )
+            .highlight_style(
+                Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .fg(Color::Cyan),
+            );
+        let mut state = ListState::default();
+        state.select(
+            (!app.seen_targets.is_empty()).then(|| selected.min(app.seen_targets.len() - 1)),
+        );
+        f.render_stateful_widget(panel, rows[0], &mut state);
+        rows[1]
+    } else {
+        log_area
+    };
+
+    // log
+    // ---------------------------------------------------------------------------------------------
+    app.log_pane = log_area;
+    if app.follow {
+        // The borders and the header take 3 rows.
+        app.follow_latest(usize::from(log_area.height.saturating_sub(3)));
+    }
+    let logs = app.visible_logs();
+
+    if app.show_raw {
+        let text = logs
+            .first()
+            .map_or_else(String::new, |(_, _, log)| match &log.raw {
+                Some(raw) => format!(
+                    "Header ({} bytes)\n{}\nPayload ({} bytes)\n{}",
+                    raw.header.len(),
+                    hex_dump(&raw.header),
+                    raw.payload.len(),
+                    hex_dump(&raw.payload)
+                ),
+                None => String::from("Raw bytes are only kept with `--keep-raw`."),
+            });
+        let raw = Paragraph::new(text).block(
+            Block::default()
+                .title(format!("Raw {:08x}", app.log))
+                .borders(Borders::ALL),
+        );
+        f.render_widget(raw, log_area);
+        return;
+    }
+
+    let merged = app.correlation.is_some() || app.merged_threads().is_some();
+    let correlated = logs.iter().any(|(_, _, log)| log.correlation.is_some());
+    let sourced = logs
+        .iter()
+        .any(|(_, _, log)| log.target.is_some() || log.module_path.is_some());
+    let located = logs.iter().any(|(_, _, log)| log.file.is_some());
+    let mut header = vec!["#"];
+    let mut widths = vec![Constraint::Length(8)];
+    if merged {
+        if app.timeline {
+            header.push("Process");
+            widths.push(Constraint::Length(16));
+        }
+        header.push("Thread");
+        widths.push(Constraint::Length(14));
+    }
+    if correlated {
+        header.push("Correlation");
+        widths.push(Constraint::Length(16));
+    }
+    if sourced {
+        header.push("Source");
+        widths.push(Constraint::Length(24));
+    }
+    if located {
+        header.push("Location");
+        widths.push(Constraint::Length(24));
+    }
+    header.extend([app.time_format.header(), "Level", "Message"]);
+    widths.extend([
+        Constraint::Length(app.time_format.width()),
+        Constraint::Length(5),
+        Constraint::Percentage(100),
+    ]);
+
+    let mut logs = logs;
+    if app.group_by_level {
+        // The sort is stable so logs remain chronological within each level.
+        logs.sort_by_key(|(_, _, log)| log.level);
+    }
+    // The rows start beneath the top border and the header, and are only clicked where they are
+    // drawn above the bottom border.
+    let mut y = log_area.y.saturating_add(2);
+    let bottom = log_area.bottom().saturating_sub(1);
+    let mut rows = Vec::with_capacity(logs.len());
+    let mut targets = Vec::new();
+    let mut section = None;
+    for (i, thread, log) in logs {
+        if app.group_by_level && section != Some(log.level) {
+            section = Some(log.level);
+            let mut cells = vec![String::new(); header.len() - 1];
+            cells.push(format!("── {} ──", log.level));
+            rows.push(Row::new(cells).style(Style::default().add_modifier(Modifier::BOLD)));
+            y = y.saturating_add(1);
+        }
+        let height = log_height(app, log);
+        if y < bottom {
+            let area = Rect::new(log_area.x, y, log_area.width, height.min(bottom - y));
+            targets.push((area, Target::Log(i)));
+        }
+        y = y.saturating_add(height);
+        rows.push(log_row(
+            app,
+            i,
+            merged.then_some(thread),
+            correlated,
+            sourced,
+            located,
+            log,
+        ));
+    }
+    app.targets.extend(targets);
+
+    let mut title = String::from("Log");
+    if let Some(correlation) = &app.correlation {
+        title.push_str(&format!(" (correlation {correlation})"));
+    } else if app.timeline {
+        title.push_str(" (timeline)");
+    } else if let [(_, first), (_, second)] = app.compared_threads[..] {
+        title.push_str(&format!(" (comparing {first:x} and {second:x})"));
+    } else if app.by_process {
+        title.push_str(" (by process)");
+    }
+    if let Some(session) = app.session {
+        title.push_str(&format!(" (session {})", app.sessions[session].name));
+    }
+    if app.group_by_level {
+        title.push_str(" (grouped by level)");
+    }
+    if let Some(filter) = &app.filter {
+        title.push_str(&format!(" (filtered by {filter:?})"));
+    }
+    if let Some(target) = &app.target_filter {
+        title.push_str(&format!(" (target {target:?})"));
+    }
+    if app.display_level != log::Level::Trace {
+        title.push_str(&format!(" ({} and above)", app.display_level));
+    }
+    if !app.target_levels.is_empty() {
+        title.push_str(" (levels by target)");
+    }
+    if app.follow {
+        title.push_str(" (following)");
+    }
+    let log = Table::new(rows)
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .header(Row::new(header))
+        .widths(&widths);
+
+    f.render_widget(log, log_area);
+}
+
+fn search<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let results = app.search_results();
+    let rows = results
+        .iter()
+        .map(|&(process, thread, _, log)| {
+            let process = &app.processes[process];
+            let thread = &process.threads[thread];
+            let message = if app.ansi {
+                ansi::strip(&log.message)
+            } else {
+                sanitize(&log.message)
+            };
+            Row::new(vec![
+                app.process_label(process),
+                app.thread_label(process.id, thread.id),
+                app.time_format.format(log.time, app.utc, app.started),
+                String::from(log.severity().name()),
+                message,
+            ])
+            .style(log.severity().style())
+        })
+        .collect::<Vec<_>>();
+
+    let mut state = TableState::default();
+    state.select((!results.is_empty()).then(|| app.search_result.min(results.len() - 1)));
+
+    let table = Table::new(rows)
+        .block(
+            Block::default()
+                .title(format!(
+                    "Search {:?} ({} results)",
+                    app.search.as_deref().unwrap_or_default(),
+                    results.len()
+                ))
+                .borders(Borders::ALL),
+        )
+        .header(Row::new(vec![
+            "Process",
+            "Thread",
+            app.time_format.header(),
+            "Level",
+            "Message",
+        ]))
+        .widths(&[
+            Constraint::Length(16),
+            Constraint::Length(24),
+            Constraint::Length(app.time_format.width()),
+            Constraint::Length(5),
+            Constraint::Percentage(100),
+        ])
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+fn overview<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+
+    let order = app.overview_order();
+    let rows = order
+        .iter()
+        .map(|&i| {
+            let process = &app.processes[i];
+            Row::new(vec![
+                format!(
+                    "{}{}",
+                    app.process_marker(process),
+                    app.process_full_label(process)
+                ),
+                process.threads.len().to_string(),
+                process.total().to_string(),
+                process.count(log::Level::Error).to_string(),
+                process.count(log::Level::Warn).to_string(),
+                process.dropped.to_string(),
+                format_duration(process.uptime()),
+                format!("{}s ago", now.saturating_sub(process.last_seen).as_secs()),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    let mut state = TableState::default();
+    state.select(
+        app.process
+            .selected()
+            .and_then(|selected| order.iter().position(|&i| i == selected)),
+    );
+
+    let table = Table::new(rows)
+        .block(
+            Block::default()
+                .title(format!(
+                    "Overview (sorted by {})",
+                    app.overview_sort.title()
+                ))
+                .borders(Borders::ALL),
+        )
+        .header(Row::new(vec![
+            "Process",
+            "Threads",
+            "Logs",
+            "Errors",
+            "Warnings",
+            "Dropped",
+            "Uptime",
+            "Last seen",
+        ]))
+        .widths(&[
+            Constraint::Length(24),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(12),
+            Constraint::Min(0),
+        ])
+        .highlight_style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .fg(Color::Cyan),
+        );
+
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+/// Returns the row of the log table for `log`, with a thread column when `thread` is given,
+/// preceded by a process column in the timeline, a correlation column when `correlated` is set, a
+/// source column when `sourced` is set and a location column when `located` is set.
+fn log_row(
+    app: &App,
+    i: usize,
+    thread: Option<(Pid, Pthread)>,
+    correlated: bool,
+    sourced: bool,
+    located: bool,
+    log: &Log,
+) -> Row<'static> {
+    let mut cells = vec![Cell::from(format!("{i:08x}"))];
+    if let Some((pid, thread)) = thread {
+        if app.timeline {
+            let process = app.process_id_map.get(&pid);
+            cells.push(Cell::from(process.map_or_else(String::new, |&process| {
+                app.process_label(&app.processes[process])
+            })));
+        }
+        cells.push(Cell::from(format!("{thread:x}")));
+    }
+    if correlated {
+        cells.push(Cell::from(log.correlation.clone().unwrap_or_default()));
+    }
+    if sourced {
+        // An explicit target is shown in bold before the module path, which it usually equals.
+        let mut source = Vec::new();
+        if let Some(target) = log.explicit_target() {
+            source.push(Span::styled(
+                target.to_string(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+        }
+        if let Some(module_path) = &log.module_path {
+            if !source.is_empty() {
+                source.push(Span::raw(" "));
+            }
+            source.push(Span::raw(module_path.clone()));
+        }
+        cells.push(Cell::from(Spans::from(source)));
+    }
+    if located {
+        cells.push(Cell::from(log.location().unwrap_or_default()));
+    }
+    let mut message = if log.message.is_empty() {
+        vec![Span::styled(
+            EMPTY_MESSAGE,
+            Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
+        )]
+    } else if app.ansi {
+        ansi::parse(&log.message)
+            .into_iter()
+            .flat_map(|(style, text)| highlight(text, style, &app.highlights))
+            .collect()
+    } else {
+        highlight(sanitize(&log.message), Style::default(), &app.highlights)
+    };
+    if log.count > 1 {
+        message.push(Span::styled(
+            format!(" (x{})", log.count),
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+    }
+    let mut lines = vec![Spans::from(message)];
+    let dim = Style::default().add_modifier(Modifier::DIM);
+    if app.expand_key_values {
+        lines.extend(log.key_values.iter().map(|(key, value)| {
+            Spans::from(Span::styled(
+                format!("  {} = {}", sanitize(key), sanitize(value)),
+                dim,
+            ))
+        }));
+    } else if !log.key_values.is_empty() {
+        let key_values = log
+            .key_values
+            .iter()
+            .map(|(key, value)| format!("{}={}", sanitize(key), sanitize(value)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        lines[0].0.push(Span::styled(format!(" {key_values}"), dim));
+    }
+    let height = log_height(app, log);
+    cells.extend([
+        Cell::from(app.time_format.format(log.time, app.utc, app.started)),
+        Cell::from(log.severity().name()),
+        Cell::from(Text::from(lines)),
+    ]);
+    Row::new(cells).height(height).style(log.severity().style())
+}
+
+/// Returns the number of lines the row of `log` takes in the log pane.
+fn log_height(app: &App, log: &Log) -> u16 {
+    if app.expand_key_values {
+        u16::try_from(log.key_values.len()).map_or(u16::MAX, |lines| lines.saturating_add(1))
+    } else {
+        1
+    }
+}
+
+/// Returns the text of the thread at index `thread` of `process` in lists, its markers and label
+/// followed by a preview of its latest log.
+fn thread_item(app: &App, process: &Process, thread: usize) -> String {
+    let thread = &process.threads[thread];
+    let key = (process.id, thread.id);
+    let pin = if app.pinned_threads.contains(&key) {
+        "*"
+    } else {
+        ""
+    };
+    let compared = if app.compared_threads.contains(&key) {
+        "+"
+    } else {
+        ""
+    };
+    let preview = thread.log.back().map_or_else(String::new, |log| {
+        let message = if log.message.is_empty() {
+            String::from(EMPTY_MESSAGE)
+        } else if app.ansi {
+            ansi::strip(&log.message)
+        } else {
+            sanitize(&log.message)
+        };
+        truncate(&message, usize::from(PREVIEW_WIDTH))
+    });
+    format!(
+        "{pin}{compared}{} {preview}",
+        app.thread_label(process.id, thread.id)
+    )
+}
+
+/// Returns the areas of the rows a bordered list of `len` items is drawn in, along with the
+/// positions of the items drawn in them, when the item at `selected` is highlighted.
+///
+/// The list is drawn with a new state each frame, so it scrolls only as far as to keep the
+/// highlighted item on its last row.
+fn list_rows(
+    area: Rect,
+    selected: Option<usize>,
+    len: usize,
+) -> impl Iterator<Item = (Rect, usize)> {
+    let height = usize::from(area.height.saturating_sub(2));
+    let offset = selected.map_or(0, |selected| (selected + 1).saturating_sub(height));
+    let width = area.width.saturating_sub(2);
+    (0..len.saturating_sub(offset).min(height)).map(move |row| {
+        // `row` is less than `height`, which fits in a `u16`.
+        let y = area.y + 1 + u16::try_from(row).unwrap();
+        (Rect::new(area.x + 1, y, width, 1), offset + row)
+    })
+}
+
+/// Returns whether the cell at `column` and `row` is within `area`.
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    (area.left()..area.right()).contains(&column) && (area.top()..area.bottom()).contains(&row)
+}
+
+/// Splits `text` into spans of `style`, additionally styling each occurrence of a keyword in
+/// `highlights`.
+///
+/// `highlights` must be in ASCII lowercase. Where keywords overlap the earliest, then longest,
+/// is highlighted.
+fn highlight(text: String, style: Style, highlights: &[String]) -> Vec<Span<'static>> {
+    if highlights.is_empty() {
+        return vec![Span::styled(text, style)];
+    }
+    // ASCII lowercasing preserves byte offsets so matches in `lower` index into `text`.
+    let lower = text.to_ascii_lowercase();
+    let highlighted = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+
+    let mut spans = Vec::new();
+    let mut start = 0;
+    while let Some((at, len)) = highlights
+        .iter()
+        .filter_map(|keyword| {
+            lower[start..]
+                .find(keyword.as_str())
+                .map(|i| (start + i, keyword.len()))
+        })
+        .min_by_key(|&(at, len)| (at, Reverse(len)))
+    {
+        spans.push(Span::styled(text[start..at].to_string(), style));
+        spans.push(Span::styled(text[at..at + len].to_string(), highlighted));
+        start = at + len;
+    }
+    spans.push(Span::styled(text[start..].to_string(), style));
+    spans
+}
+
+/// Formats `bytes` as lines of 16 hex bytes, each prefixed by its offset and followed by its
+/// printable ASCII characters.
+fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex = chunk
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        char::from(byte)
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            format!("{:08x}  {hex:<47}  {ascii}\n", i * 16)
+        })
+        .collect()
+}
+
+/// Escapes control characters in `message` so they cannot affect the terminal.
+fn sanitize(message: &str) -> String {
+    let mut sanitized = String::with_capacity(message.len());
+    for c in message.chars() {
+        if c.is_control() {
+            sanitized.extend(c.escape_debug());
+        } else {
+            sanitized.push(c);
+        }
+    }
+    sanitized
+}
+
+/// Truncates `text` to at most `width` characters, marking where it was cut.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() > width {
+        text.chars()
+            .take(width.saturating_sub(1))
+            .chain(std::iter::once('…'))
+            .collect()
+    } else {
+        text.to_owned()
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// A stream a client sends logs over, either transport carries the same wire format.
+trait ClientStream: Read + AsFd + Send + 'static {
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()>;
+
+    /// Returns whether the client is on this machine, so the process ids it sends are those of
+    /// processes in `/proc`.
+    fn is_local(&self) -> bool;
+}
+
+impl ClientStream for UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        UnixStream::set_nonblocking(self, nonblocking)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+impl ClientStream for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+/// The id of the next connection accepted, unique across listeners.
+static NEXT_CONNECTION: AtomicUsize = AtomicUsize::new(0);
+
+/// Accepts connections from `incoming`, receiving logs in `wire_format` from each on a thread of
+/// its own.
+fn accept<S: ClientStream>(
+    incoming: impl Iterator<Item = std::io::Result<S>>,
+    app: Arc<RwLock<App>>,
+    wire_format: WireFormat,
+) {
+    for stream in incoming {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                app.write().unwrap().system_log(
+                    log::Level::Warn,
+                    format!("Failed to accept connection: {err}"),
+                );
+                continue;
+            }
+        };
+        let id = NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed);
+        app.write()
+            .unwrap()
+            .system_log(log::Level::Debug, format!("Accepted connection {id:08x}"));
+
+        let app_clone = app.clone();
+        std::thread::spawn(move || {
+            match wire_format {
+                WireFormat::Binary => handle_stream(stream, id, app_clone.clone()),
+                WireFormat::Json => {
+                    let local = stream.is_local();
+                    json::handle_stream(stream, local, id, app_clone.clone());
+                }
+            }
+            app_clone
+                .write()
+                .unwrap()
+                .system_log(log::Level::Debug, format!("Closed connection {id:08x}"));
+        });
+    }
+}
+
+/// Fills `buf` from the non-blocking `stream`, waiting on `epoll` whenever no data is available.
+///
+/// Returns `Ok(false)` if the stream is closed before any of `buf` is read.
+///
+/// # Errors
+///
+/// When the stream is closed part way through `buf` ([`std::io::ErrorKind::UnexpectedEof`]), or
+/// failing to read from it.
+fn fill(stream: &mut impl Read, epoll: &Epoll, buf: &mut [u8]) -> std::io::Result<bool> {
+    let mut index = 0;
+    while index < buf.len() {
+        match stream.read(&mut buf[index..]) {
+            Ok(0) if index == 0 => return Ok(false),
+            Ok(0) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("closed after {index} of {} bytes", buf.len()),
+                ))
+            }
+            Ok(n) => index += n,
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                // The wait is never restarted after a signal such as `SIGUSR1` is handled, which
+                // is a spurious wakeup like any other, the read is retried either way.
+                match epoll.wait(&mut [EpollEvent::empty()], -1) {
+                    Ok(_) | Err(Errno::EINTR) => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(true)
+}
+
+/// Reads and discards `length` bytes from the non-blocking `stream`, see [`fill`].
+///
+/// # Errors
+///
+/// When the stream is closed before `length` bytes are read
+/// ([`std::io::ErrorKind::UnexpectedEof`]), or failing to read from it.
+fn skip(stream: &mut impl Read, epoll: &Epoll, mut length: u64) -> std::io::Result<()> {
+    let mut buf = [0; DEFAULT_CAPACITY];
+    while length > 0 {
+        let chunk = usize::try_from(length).map_or(buf.len(), |length| length.min(buf.len()));
+        if !fill(stream, epoll, &mut buf[..chunk])? {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        length -= u64::try_from(chunk).unwrap();
+    }
+    Ok(())
 }
 
-fn handle_stream(mut stream: UnixStream, id: usize, app: Arc<RwLock<App>>) {
+fn handle_stream(mut stream: impl ClientStream, id: usize, app: Arc<RwLock<App>>) {
     stream.set_nonblocking(true).unwrap();
+    let local = stream.is_local();
 
-    let mut array = [0; size_of::<LogData>()];
+    let mut array = [0; Header::LEN];
     let mut data = Vec::with_capacity(DEFAULT_CAPACITY);
 
     let epoll = Epoll::new(EpollCreateFlags::empty()).unwrap();
@@ -382,74 +3913,1075 @@ fn handle_stream(mut stream: UnixStream, id: usize, app: Arc<RwLock<App>>) {
         )
         .unwrap();
 
+    // Handshake
+    // ---------------------------------------------------------------------------------------------
+    let mut handshake = [0; 2];
+    if !matches!(fill(&mut stream, &epoll, &mut handshake), Ok(true)) {
+        return;
+    }
+    if let Err(err) = logger_wire::check_handshake(handshake) {
+        app.write().unwrap().system_log(
+            log::Level::Warn,
+            format!("Rejected connection {id:08x}: {err}"),
+        );
+        return;
+    }
+
+    // The processes which have logged over this connection.
+    let mut pids = HashSet::new();
+    // The correlation id attached to the logs of each thread.
+    let mut correlations = HashMap::new();
+    // The key-value pairs of the next log of each thread.
+    let mut key_values = HashMap::new();
+    // Notes a connection lost part way through a frame, rather than closed between frames.
+    let lost = |err: std::io::Error| {
+        app.write().unwrap().system_log(
+            log::Level::Warn,
+            format!("Lost connection {id:08x} part way through a frame: {err}"),
+        );
+    };
+
     loop {
         // Fixed size data
         // -----------------------------------------------------------------------------------------
-        let mut array_index = non_blocking(stream.read(&mut array)).unwrap();
-        while array_index < array.len() {
-            epoll.wait(&mut [EpollEvent::empty()], -1).unwrap();
-            array_index += stream.read(&mut array[array_index..]).unwrap();
+        match fill(&mut stream, &epoll, &mut array) {
+            Ok(true) => {}
+            // The client closed the connection between frames.
+            Ok(false) => break,
+            Err(err) => {
+                lost(err);
+                break;
+            }
         }
-        let log_data = unsafe { std::mem::transmute::<_, LogData>(array) };
+        let header = Header::decode(&array).unwrap();
+        let pid = Pid::from_raw(header.pid);
+        let pthread = header.pthread;
 
         // Dynamic size data
         // -----------------------------------------------------------------------------------------
-        data.resize(log_data.length, 0);
+        // A misbehaving client could otherwise have the server allocate any amount, the frame is
+        // skipped as a whole so the next one is read from its start.
+        if header.length > app.read().unwrap().max_frame_length {
+            app.write().unwrap().system_log(
+                log::Level::Warn,
+                format!(
+                    "Skipped a frame of {} bytes on connection {id:08x}, longer than \
+                     --max-frame-length",
+                    header.length
+                ),
+            );
+            if let Err(err) = skip(&mut stream, &epoll, header.length) {
+                lost(err);
+                break;
+            }
+            continue;
+        }
+        let Ok(length) = usize::try_from(header.length) else {
+            break;
+        };
+        data.resize(length, 0);
+
+        match fill(&mut stream, &epoll, &mut data) {
+            Ok(true) => {}
+            Ok(false) => {
+                lost(std::io::ErrorKind::UnexpectedEof.into());
+                break;
+            }
+            Err(err) => {
+                lost(err);
+                break;
+            }
+        }
+
+        let mut app = app.write().unwrap();
+
+        // Compression
+        // -----------------------------------------------------------------------------------------
+        // The bytes as received are kept for `--keep-raw`, the rest of the frame is decoded from
+        // `data`.
+        let mut received = None;
+        if header.flags & COMPRESSED != 0 {
+            let limit = usize::try_from(app.max_frame_length).unwrap_or(usize::MAX);
+            match logger_wire::decompress(&data, limit) {
+                Ok(decompressed) => received = Some(std::mem::replace(&mut data, decompressed)),
+                Err(err) => {
+                    app.system_log(
+                        log::Level::Warn,
+                        format!("Skipped a frame on connection {id:08x}: {err}"),
+                    );
+                    continue;
+                }
+            }
+        }
+
+        // Status
+        // -----------------------------------------------------------------------------------------
+        if header.kind == STATUS_FRAME {
+            if let (Ok(dropped), Some(&process)) = (
+                <[u8; 8]>::try_from(data.as_slice()),
+                app.process_id_map.get(&pid),
+            ) {
+                let dropped = u64::from_le_bytes(dropped);
+                let process = &mut app.processes[process];
+                if dropped > process.dropped {
+                    process.dropped = dropped;
+                    process.last_drop = Some(Instant::now());
+                }
+            }
+            continue;
+        }
+
+        // Sampling
+        // -----------------------------------------------------------------------------------------
+        if header.kind == SAMPLING_FRAME {
+            if let (Some(&process), Ok(rates)) = (
+                app.process_id_map.get(&pid),
+                <[u8; 20]>::try_from(data.as_slice()),
+            ) {
+                let sampling = &mut app.processes[process].sampling;
+                for (rate, bytes) in sampling.iter_mut().zip(rates.chunks_exact(4)) {
+                    *rate = u32::from_le_bytes(bytes.try_into().unwrap()).max(1);
+                }
+            }
+            continue;
+        }
+
+        // Correlation
+        // -----------------------------------------------------------------------------------------
+        if header.kind == CORRELATION_FRAME {
+            let key = (pid, pthread);
+            match std::str::from_utf8(&data) {
+                Ok(id) if !id.is_empty() => {
+                    correlations.insert(key, String::from(id));
+                }
+                _ => {
+                    correlations.remove(&key);
+                }
+            }
+            continue;
+        }
+        // Key-value pairs
+        // -----------------------------------------------------------------------------------------
+        if header.kind == KEY_VALUES_FRAME {
+            if let Ok(pairs) = logger_wire::decode_key_values(&data) {
+                key_values.insert((pid, pthread), pairs);
+            }
+            continue;
+        }
+
+        // Process name
+        // -----------------------------------------------------------------------------------------
+        if header.kind == PROCESS_NAME_FRAME {
+            let name = String::from_utf8_lossy(&data);
+            if !name.is_empty() {
+                app.process_names.insert(pid, name.into_owned());
+            }
+            continue;
+        }
+
+        // Process context
+        // -----------------------------------------------------------------------------------------
+        if header.kind == CONTEXT_FRAME {
+            if let Ok(pairs) = logger_wire::decode_key_values(&data) {
+                app.process_contexts.insert(pid, pairs);
+            }
+            continue;
+        }
 
-        let mut data_index = non_blocking(stream.read(&mut data)).unwrap();
-        while data_index < data.len() {
-            epoll.wait(&mut [EpollEvent::empty()], -1).unwrap();
-            data_index += stream.read(&mut data[data_index..]).unwrap();
+        // Thread name
+        // -----------------------------------------------------------------------------------------
+        if header.kind == THREAD_NAME_FRAME {
+            // The id may have belonged to an exited thread, whose name no longer applies.
+            let name = String::from_utf8_lossy(&data);
+            if name.is_empty() {
+                app.thread_names.remove(&(pid, pthread));
+            } else {
+                app.thread_names.insert((pid, pthread), name.into_owned());
+            }
+            continue;
+        }
+        // Frames of kinds added by newer clients are skipped rather than decoded as logs.
+        if header.kind != LOG_FRAME {
+            app.system_log(
+                log::Level::Warn,
+                format!(
+                    "Skipped a frame of unknown kind {} on connection {id:08x}",
+                    header.kind
+                ),
+            );
+            continue;
         }
 
-        let message = String::from(std::str::from_utf8(&data).unwrap());
+        // The message starts with the target, module path and file of the record.
+        let (target, rest) = data.split_at(usize::from(header.target_length).min(data.len()));
+        let (module_path, rest) =
+            rest.split_at(usize::from(header.module_path_length).min(rest.len()));
+        let (file, message) = rest.split_at(usize::from(header.file_length).min(rest.len()));
+        let source = |source: &[u8]| {
+            std::str::from_utf8(source)
+                .ok()
+                .filter(|source| !source.is_empty())
+                .map(String::from)
+        };
+        let (target, module_path, file) = (source(target), source(module_path), source(file));
+        // A message which is not UTF-8 is still shown, with its invalid bytes replaced.
+        let message = String::from_utf8_lossy(message).into_owned();
+        let Some(message) = app.nul_policy.apply(message) else {
+            continue;
+        };
 
         // Add data
         // -----------------------------------------------------------------------------------------
-        let mut app = app.write().unwrap();
-        let time = Duration::new(log_data.secs, log_data.nanos);
+        let Some(time) = log_time(header.secs, header.nanos) else {
+            app.system_log(
+                log::Level::Warn,
+                format!("Skipped a frame on connection {id:08x} with an invalid time"),
+            );
+            continue;
+        };
+        let severity = Severity::from_number(i64::from(header.level));
         let log = Log {
             time,
+            sequence: Some(header.sequence),
             message,
-            level: log_data.level,
+            count: 1,
+            level: severity.level,
+            fatal: severity.fatal,
+            correlation: correlations.get(&(pid, pthread)).cloned(),
+            target,
+            module_path,
+            file,
+            line: (header.line != 0).then_some(header.line),
+            key_values: key_values.remove(&(pid, pthread)).unwrap_or_default(),
+            raw: app.keep_raw.then(|| RawFrame {
+                header: array.to_vec(),
+                payload: received.unwrap_or_else(|| data.clone()),
+            }),
         };
+        // Only processes which are kept are connected, so they can be disconnected in turn.
+        if app.add_log(pid, pthread, log) && pids.insert(pid) {
+            app.connect(pid, local);
+        }
+    }
 
-        if let Some(process) = app
-            .process_id_map
-            .get(&log_data.pid)
-            .copied()
-            .map(|i| &mut app.processes[i])
+    app.write().unwrap().disconnect(&pids);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, thread::JoinHandle};
+
+    use super::*;
+
+    /// Encodes a log frame of `message` from the thread `pthread` of the process `pid`.
+    fn log_frame(pid: i32, pthread: Pthread, message: &[u8]) -> Vec<u8> {
+        let header = Header {
+            secs: 1,
+            nanos: 0,
+            sequence: 0,
+            pid,
+            pthread,
+            length: u64::try_from(message.len()).unwrap(),
+            target_length: 0,
+            module_path_length: 0,
+            file_length: 0,
+            line: 0,
+            level: log::Level::Info as u8,
+            kind: LOG_FRAME,
+            flags: 0,
+        };
+        let mut frame = header.encode().to_vec();
+        frame.extend_from_slice(message);
+        frame
+    }
+
+    /// Receives logs on a new connection to `app`, returning the client end of the connection,
+    /// which has already sent the handshake, and the thread receiving on the other.
+    fn connect(app: &Arc<RwLock<App>>) -> (UnixStream, JoinHandle<()>) {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        client.write_all(&logger_wire::handshake()).unwrap();
+        let app = app.clone();
+        (
+            client,
+            std::thread::spawn(move || handle_stream(server, 0, app)),
+        )
+    }
+
+    /// Returns the messages of the logs of the thread `pthread` of the process `pid`.
+    fn messages(app: &App, pid: Pid, pthread: Pthread) -> Vec<String> {
+        let process = &app.processes[app.process_id_map[&pid]];
+        let thread = &process.threads[process.thread_id_map[&pthread]];
+        thread.log.iter().map(|log| log.message.clone()).collect()
+    }
+
+    /// Returns the events the server logged about itself.
+    fn system_logs(app: &App) -> Vec<String> {
+        if app.process_id_map.contains_key(&app.server) {
+            messages(app, app.server, SYSTEM_THREAD)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns an app without any logs which records every event of the server.
+    fn new_app() -> Arc<RwLock<App>> {
+        let mut app = App::new();
+        app.system_log = log::LevelFilter::Trace;
+        Arc::new(RwLock::new(app))
+    }
+
+    /// Returns an info log of `message`.
+    fn info(message: &str) -> Log {
+        Log {
+            time: Duration::from_secs(1),
+            sequence: None,
+            level: log::Level::Info,
+            fatal: false,
+            message: message.to_string(),
+            count: 1,
+            correlation: None,
+            target: None,
+            module_path: None,
+            file: None,
+            line: None,
+            key_values: Vec::new(),
+            raw: None,
+        }
+    }
+
+    /// Returns the messages of the logs shown in the log pane.
+    fn visible_messages(app: &App) -> Vec<String> {
+        app.visible_logs()
+            .into_iter()
+            .map(|(_, _, log)| log.message.clone())
+            .collect()
+    }
+
+    /// Returns each way of navigating the processes, threads and logs, along with its name.
+    fn navigation() -> [(&'static str, fn(&mut App)); 10] {
+        [
+            ("next_process", App::next_process),
+            ("previous_process", App::previous_process),
+            ("next_thread", App::next_thread),
+            ("previous_thread", App::previous_thread),
+            ("next_overview", App::next_overview),
+            ("previous_overview", App::previous_overview),
+            ("next_log", |app| app.next_log(1)),
+            ("previous_log", |app| app.previous_log(1)),
+            ("page_down", App::page_down),
+            ("page_up", App::page_up),
+        ]
+    }
+
+    /// Asserts the selected process, thread and log exist and the log pane shows logs.
+    fn assert_consistent(app: &App, step: &str) {
+        let process = app.process.selected().expect(step);
+        let thread = app.thread.selected().expect(step);
+        assert!(
+            app.processes[process].threads.get(thread).is_some(),
+            "{step}"
+        );
+        let (first, last) = app.log_bounds().expect(step);
+        assert!((first..=last).contains(&app.log), "{step}");
+        assert!(!app.visible_logs().is_empty(), "{step}");
+    }
+
+    #[test]
+    fn frame_split_across_writes() {
+        let app = new_app();
+        let (mut client, handle) = connect(&app);
+        let frame = log_frame(1, 2, b"split across writes");
+        // Split in the middle of the header and in the middle of the message.
+        for chunk in [
+            &frame[..10],
+            &frame[10..Header::LEN + 5],
+            &frame[Header::LEN + 5..],
+        ] {
+            client.write_all(chunk).unwrap();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        drop(client);
+        handle.join().unwrap();
+
+        let app = app.read().unwrap();
+        assert_eq!(messages(&app, Pid::from_raw(1), 2), ["split across writes"]);
+        assert!(system_logs(&app).is_empty());
+    }
+
+    #[test]
+    fn frame_split_across_tcp_segments() {
+        let app = new_app();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.set_nodelay(true).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let handle = {
+            let app = app.clone();
+            std::thread::spawn(move || handle_stream(server, 0, app))
+        };
+
+        let mut bytes = logger_wire::handshake().to_vec();
+        bytes.extend(log_frame(1, 2, b"first"));
+        bytes.extend(log_frame(1, 2, b"second"));
+        for chunk in bytes.chunks(7) {
+            client.write_all(chunk).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        drop(client);
+        handle.join().unwrap();
+
+        assert_eq!(
+            messages(&app.read().unwrap(), Pid::from_raw(1), 2),
+            ["first", "second"]
+        );
+    }
+
+    #[test]
+    fn frame_trickled() {
+        let app = new_app();
+        let (mut client, handle) = connect(&app);
+        for byte in log_frame(1, 2, b"one byte at a time") {
+            client.write_all(&[byte]).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        drop(client);
+        handle.join().unwrap();
+
+        let app = app.read().unwrap();
+        assert_eq!(messages(&app, Pid::from_raw(1), 2), ["one byte at a time"]);
+        assert!(system_logs(&app).is_empty());
+    }
+
+    #[test]
+    fn frame_too_long_skipped() {
+        let app = new_app();
+        app.write().unwrap().max_frame_length = 16;
+        let (mut client, handle) = connect(&app);
+        client.write_all(&log_frame(1, 2, &[b'a'; 32])).unwrap();
+        client.write_all(&log_frame(1, 2, b"short")).unwrap();
+        drop(client);
+        handle.join().unwrap();
+
+        let app = app.read().unwrap();
+        assert_eq!(messages(&app, Pid::from_raw(1), 2), ["short"]);
+        assert_eq!(
+            system_logs(&app),
+            [
+                "Skipped a frame of 32 bytes on connection 00000000, longer than \
+                 --max-frame-length"
+            ]
+        );
+    }
+
+    #[test]
+    fn frame_length_untrusted() {
+        let app = new_app();
+        app.write().unwrap().max_frame_length = 1024 * 1024;
+        let (mut client, handle) = connect(&app);
+        // Allocating the frame would abort the test.
+        let mut header = Header::decode(&log_frame(1, 2, b"")).unwrap();
+        header.length = 1 << 40;
+        client.write_all(&header.encode()).unwrap();
+        drop(client);
+        handle.join().unwrap();
+
+        let app = app.read().unwrap();
+        assert!(!app.process_id_map.contains_key(&Pid::from_raw(1)));
+        let warnings = system_logs(&app);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("Skipped a frame of 1099511627776 bytes"));
+        assert!(warnings[1].starts_with("Lost connection 00000000 part way through a frame"));
+    }
+
+    #[test]
+    fn message_compressed() {
+        let app = new_app();
+        let (mut client, handle) = connect(&app);
+        let message = b"repeated ".repeat(1000);
+        let compressed = logger_wire::compress(&message);
+        let mut header = Header::decode(&log_frame(1, 2, b"")).unwrap();
+        header.length = u64::try_from(compressed.len()).unwrap();
+        header.flags = COMPRESSED;
+        client.write_all(&header.encode()).unwrap();
+        client.write_all(&compressed).unwrap();
+        // Not valid deflate data.
+        header.length = 64;
+        client.write_all(&header.encode()).unwrap();
+        client.write_all(&[0xff; 64]).unwrap();
+        client.write_all(&log_frame(1, 2, b"after")).unwrap();
+        drop(client);
+        handle.join().unwrap();
+
+        let app = app.read().unwrap();
+        assert_eq!(
+            messages(&app, Pid::from_raw(1), 2),
+            [String::from_utf8(message).unwrap(), String::from("after")]
+        );
+        assert_eq!(
+            system_logs(&app),
+            ["Skipped a frame on connection 00000000: invalid compressed message"]
+        );
+    }
+
+    #[test]
+    fn merged_logs_ordered_by_sequence() {
+        let app = new_app();
+        let (mut client, handle) = connect(&app);
+        // Sent at the same time, and received in the opposite order to which they were numbered.
+        for (pthread, sequence, message) in [(2, 1, "second"), (3, 0, "first")] {
+            let frame = log_frame(1, pthread, message.as_bytes());
+            let mut header = Header::decode(&frame[..Header::LEN]).unwrap();
+            header.sequence = sequence;
+            client.write_all(&header.encode()).unwrap();
+            client.write_all(&frame[Header::LEN..]).unwrap();
+        }
+        drop(client);
+        handle.join().unwrap();
+
+        let mut app = app.write().unwrap();
+        app.by_process = true;
+        let logs = app.merged_logs().unwrap();
+        let messages = logs
+            .iter()
+            .map(|(_, log)| log.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, ["first", "second"]);
+    }
+
+    #[test]
+    fn merged_logs_ordered_by_sequence_within_process() {
+        let mut app = App::new();
+        // The clock of the first process steps back between its logs.
+        for (pid, sequence, secs, message) in
+            [(1, 0, 5, "first"), (1, 1, 3, "second"), (2, 0, 4, "other")]
         {
-            if let Some(thread) = process
-                .thread_id_map
-                .get(&log_data.pthread)
-                .map(|i| &mut process.threads[*i])
-            {
-                thread.log.push(log);
-            } else {
-                process
-                    .thread_id_map
-                    .insert(log_data.pthread, process.threads.len());
-                process.threads.push(Thread {
-                    id: log_data.pthread,
-                    log: vec![log],
+            let mut log = info(message);
+            log.sequence = Some(sequence);
+            log.time = Duration::from_secs(secs);
+            app.add_log(Pid::from_raw(pid), 2, log);
+        }
+
+        app.timeline = true;
+        assert_eq!(visible_messages(&app), ["other", "first", "second"]);
+        app.set_search("s");
+        let results = app
+            .search_results()
+            .into_iter()
+            .map(|(_, _, _, log)| log.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(results, ["first", "second"]);
+    }
+
+    #[test]
+    fn search_results_follow_new_logs() {
+        let mut app = App::new();
+        let results = |app: &App| {
+            app.search_results()
+                .into_iter()
+                .map(|(_, _, _, log)| log.message.clone())
+                .collect::<Vec<_>>()
+        };
+        app.add_log(Pid::from_raw(1), 2, info("Found"));
+        app.set_search("found");
+        assert_eq!(results(&app), ["Found"]);
+
+        app.add_log(Pid::from_raw(1), 2, info("missing"));
+        app.add_log(Pid::from_raw(2), 2, info("also found"));
+        assert_eq!(results(&app), ["Found", "also found"]);
+        app.set_search("missing");
+        assert_eq!(results(&app), ["missing"]);
+    }
+
+    #[test]
+    fn message_invalid_utf8() {
+        let app = new_app();
+        let (mut client, handle) = connect(&app);
+        client
+            .write_all(&log_frame(1, 2, b"bad \xff byte"))
+            .unwrap();
+        client.write_all(&log_frame(1, 2, b"next")).unwrap();
+        drop(client);
+        handle.join().unwrap();
+
+        assert_eq!(
+            messages(&app.read().unwrap(), Pid::from_raw(1), 2),
+            ["bad \u{fffd} byte", "next"]
+        );
+    }
+
+    #[test]
+    fn message_nul() {
+        for (policy, expected) in [
+            (NulPolicy::Keep, Some("a\0b")),
+            (NulPolicy::Strip, Some("ab")),
+            (NulPolicy::Reject, None),
+        ] {
+            let app = new_app();
+            app.write().unwrap().nul_policy = policy;
+            let (mut client, handle) = connect(&app);
+            client.write_all(&log_frame(1, 2, b"a\0b")).unwrap();
+            client.write_all(&log_frame(3, 4, b"after")).unwrap();
+            drop(client);
+            handle.join().unwrap();
+
+            let app = app.read().unwrap();
+            assert_eq!(
+                app.process_id_map
+                    .contains_key(&Pid::from_raw(1))
+                    .then(|| messages(&app, Pid::from_raw(1), 2)),
+                expected.map(|message| vec![String::from(message)]),
+                "{policy:?}"
+            );
+            assert_eq!(messages(&app, Pid::from_raw(3), 4), ["after"], "{policy:?}");
+        }
+        // Kept NUL bytes are escaped when shown.
+        assert_eq!(sanitize("a\0b"), "a\\0b");
+    }
+
+    #[test]
+    fn messages_empty() {
+        let app = new_app();
+        let (mut client, handle) = connect(&app);
+        let mut bytes = Vec::new();
+        for _ in 0..3 {
+            bytes.extend(log_frame(1, 2, b""));
+        }
+        bytes.extend(log_frame(1, 2, b"after"));
+        // Sent in one write so the frames are read back to back.
+        client.write_all(&bytes).unwrap();
+        drop(client);
+        handle.join().unwrap();
+
+        let app = app.read().unwrap();
+        assert_eq!(messages(&app, Pid::from_raw(1), 2), ["", "", "", "after"]);
+        assert!(system_logs(&app).is_empty());
+    }
+
+    /// Waits for the thread `handle` to return, failing if it panics or takes too long.
+    fn join(handle: JoinHandle<()>) {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || sender.send(handle.join()).unwrap());
+        receiver
+            .recv_timeout(Duration::from_secs(5))
+            .expect("thread did not return")
+            .expect("thread panicked");
+    }
+
+    #[test]
+    fn client_closes() {
+        let app = new_app();
+        let (mut client, handle) = connect(&app);
+        client.write_all(&log_frame(1, 2, b"goodbye")).unwrap();
+        drop(client);
+        join(handle);
+
+        let app = app.read().unwrap();
+        assert_eq!(messages(&app, Pid::from_raw(1), 2), ["goodbye"]);
+        let process = &app.processes[app.process_id_map[&Pid::from_raw(1)]];
+        assert_eq!(process.connections, 0);
+        assert!(process.disconnected_at.is_some());
+        assert!(system_logs(&app).is_empty());
+    }
+
+    #[test]
+    fn client_closes_part_way_through_frame() {
+        let app = new_app();
+        let (mut client, handle) = connect(&app);
+        client.write_all(&log_frame(1, 2, b"complete")).unwrap();
+        let frame = log_frame(1, 2, b"incomplete");
+        client.write_all(&frame[..frame.len() - 1]).unwrap();
+        drop(client);
+        join(handle);
+
+        let app = app.read().unwrap();
+        assert_eq!(messages(&app, Pid::from_raw(1), 2), ["complete"]);
+        let warnings = system_logs(&app);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("Lost connection 00000000 part way through a frame"));
+    }
+
+    #[test]
+    fn first_log_selects_its_process() {
+        let app = Arc::new(RwLock::new(App::new()));
+        // Connections are numbered apart from the order their processes are added in.
+        let [(mut early, early_handle), (mut late, late_handle)] = [7, 3].map(|id| {
+            let (mut client, server) = UnixStream::pair().unwrap();
+            client.write_all(&logger_wire::handshake()).unwrap();
+            let app = app.clone();
+            (
+                client,
+                std::thread::spawn(move || handle_stream(server, id, app)),
+            )
+        });
+        late.write_all(&log_frame(20, 21, b"first")).unwrap();
+        while app.read().unwrap().processes.is_empty() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        early.write_all(&log_frame(10, 11, b"second")).unwrap();
+        drop((early, late));
+        join(early_handle);
+        join(late_handle);
+
+        let app = app.read().unwrap();
+        let process = &app.processes[app.process.selected().unwrap()];
+        assert_eq!(process.id, Pid::from_raw(20));
+        assert_eq!(process.threads[app.thread.selected().unwrap()].id, 21);
+    }
+
+    #[test]
+    fn first_log_selects_its_process_concurrently() {
+        for _ in 0..10 {
+            let app = Arc::new(RwLock::new(App::new()));
+            let threads = [(7, 10), (3, 20)].map(|(id, pid)| {
+                let (mut client, server) = UnixStream::pair().unwrap();
+                let app = app.clone();
+                let handle = std::thread::spawn(move || handle_stream(server, id, app));
+                let writer = std::thread::spawn(move || {
+                    client.write_all(&logger_wire::handshake()).unwrap();
+                    for _ in 0..10 {
+                        client.write_all(&log_frame(pid, 1, b"concurrent")).unwrap();
+                    }
                 });
-            }
-        } else {
-            let len = app.processes.len();
-            app.process_id_map.insert(log_data.pid, len);
-            app.processes.push(Process {
-                id: log_data.pid,
-                thread_id_map: std::iter::once((log_data.pthread, 0)).collect(),
-                threads: vec![Thread {
-                    id: log_data.pthread,
-                    log: vec![log],
-                }],
+                (handle, writer)
             });
+            for (handle, writer) in threads {
+                writer.join().unwrap();
+                join(handle);
+            }
+
+            let app = app.read().unwrap();
+            assert_eq!(app.processes.len(), 2);
+            let process = &app.processes[app.process.selected().unwrap()];
+            assert!(process
+                .threads
+                .get(app.thread.selected().unwrap())
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn scrolling_never_empties_view() {
+        let mut app = App::new();
+        let pid = Pid::from_raw(1);
+        for i in 0..20 {
+            app.add_log(pid, 2, info(&format!("log {i}")));
+        }
+        // 10 logs to a page.
+        app.log_pane = Rect::new(0, 0, 80, 13);
+
+        app.next_log(19);
+        assert_eq!(visible_messages(&app), ["log 19"]);
+        app.next_log(1);
+        assert_eq!(visible_messages(&app), ["log 19"]);
+        app.page_down();
+        app.page_down();
+        assert_eq!(visible_messages(&app), ["log 19"]);
+
+        // The oldest logs are removed while the pane is scrolled to the top.
+        app.previous_log(20);
+        assert_eq!(visible_messages(&app).len(), 20);
+        app.max_logs_per_thread = 5;
+        for i in 20..25 {
+            app.add_log(pid, 2, info(&format!("log {i}")));
+        }
+        app.next_log(1);
+        assert_eq!(
+            visible_messages(&app),
+            ["log 21", "log 22", "log 23", "log 24"]
+        );
+        app.page_down();
+        assert_eq!(visible_messages(&app), ["log 24"]);
+    }
+
+    #[test]
+    fn navigation_keeps_selection_consistent() {
+        let mut app = App::new();
+        // The first process has more threads, with more logs, than the second.
+        let (long, short) = (Pid::from_raw(1), Pid::from_raw(2));
+        for thread in 0..3 {
+            for i in 0..20 {
+                app.add_log(long, thread, info(&format!("{thread} {i}")));
+            }
+        }
+        app.add_log(short, 0, info("short"));
+        app.log_pane = Rect::new(0, 0, 80, 13);
+        app.expanded.insert(long);
+
+        for tree in [false, true] {
+            app.tree = tree;
+            for (step, navigate) in navigation() {
+                // From the end of the last thread of the first process.
+                app.select_thread(0, 2);
+                app.next_log(19);
+                navigate(&mut app);
+                assert_consistent(&app, step);
+            }
+        }
+
+        // Removing the selected process selects the one after it.
+        app.tree = false;
+        app.select_thread(0, 2);
+        app.next_log(19);
+        app.remove_process(0);
+        assert_consistent(&app, "remove_process");
+        assert_eq!(app.processes[app.process.selected().unwrap()].id, short);
+        assert_eq!(visible_messages(&app), ["short"]);
+    }
+
+    #[test]
+    fn navigation_without_processes() {
+        for tree in [false, true] {
+            for (step, navigate) in navigation() {
+                let mut app = App::new();
+                app.tree = tree;
+                navigate(&mut app);
+                assert_eq!(app.process.selected(), None, "{step}");
+                assert_eq!(app.thread.selected(), None, "{step}");
+                assert_eq!(app.log, 0, "{step}");
+            }
+        }
+    }
+
+    #[test]
+    fn navigation_without_selection() {
+        for (step, navigate) in navigation() {
+            let mut app = App::new();
+            for pid in [1, 2] {
+                app.add_log(Pid::from_raw(pid), 0, info("unselected"));
+            }
+            app.process.select(None);
+            app.thread.select(None);
+            navigate(&mut app);
+            match step {
+                // Selecting a process selects its first thread.
+                "next_process" | "previous_process" | "next_overview" | "previous_overview" => {
+                    assert_consistent(&app, step);
+                }
+                // There is no process to select a thread or log of.
+                _ => assert_eq!(app.thread.selected(), None, "{step}"),
+            }
+        }
+
+        // With a process selected, its first thread is.
+        for (step, navigate) in [
+            ("next_thread", App::next_thread as fn(&mut App)),
+            ("previous_thread", App::previous_thread),
+        ] {
+            let mut app = App::new();
+            for thread in [1, 2] {
+                app.add_log(Pid::from_raw(1), thread, info("unselected"));
+            }
+            app.thread.select(None);
+            navigate(&mut app);
+            assert_eq!(app.thread.selected(), Some(0), "{step}");
+            assert_consistent(&app, step);
+        }
+    }
+
+    #[test]
+    fn render_stale_selection() {
+        for view in [View::Detail, View::Overview, View::Search] {
+            let mut app = App::new();
+            for pid in 1..=3 {
+                for thread in 0..3 {
+                    for i in 0..10 {
+                        app.add_log(Pid::from_raw(pid), thread, info(&format!("log {i}")));
+                    }
+                }
+            }
+            app.set_search("log");
+            app.search_result = 89;
+            app.view = view;
+            app.select_thread(2, 2);
+            app.next_log(9);
+            // Processes are removed from under the selection, as by another thread between frames.
+            app.processes.truncate(1);
+            app.process_id_map.retain(|_, &mut i| i < 1);
+            app.processes[0].threads.truncate(1);
+            app.processes[0].thread_id_map.retain(|_, &mut i| i < 1);
+
+            let app = Arc::new(RwLock::new(app));
+            let mut terminal = Terminal::new(tui::backend::TestBackend::new(80, 24)).unwrap();
+            terminal.draw(|f| ui(f, app.clone())).unwrap();
+
+            let app = app.read().unwrap();
+            assert_eq!(app.process.selected(), Some(0), "{view:?}");
+            assert_eq!(app.thread.selected(), Some(0), "{view:?}");
+            assert!(app.log <= 9, "{view:?}");
+        }
+    }
+
+    #[test]
+    fn key_values_shown_and_hidden() {
+        let key_values = |app: &mut App| {
+            let mut log = info("keyed");
+            log.key_values = ["user", "request", "internal"]
+                .map(|key| (key.to_string(), String::from("1")))
+                .to_vec();
+            app.add_log(Pid::from_raw(1), 2, log);
+            let thread = app.selected_thread().unwrap();
+            let log = thread.get(thread.last()).unwrap();
+            log.key_values
+                .iter()
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let mut app = App::new();
+        assert_eq!(key_values(&mut app), ["user", "request", "internal"]);
+        // Keys which are never logged are ignored.
+        app.shown_keys = HashSet::from(["user", "request", "unknown"].map(String::from));
+        assert_eq!(key_values(&mut app), ["user", "request"]);
+        app.hidden_keys = HashSet::from(["request", "missing"].map(String::from));
+        assert_eq!(key_values(&mut app), ["user"]);
+        app.shown_keys.clear();
+        assert_eq!(key_values(&mut app), ["user", "internal"]);
+    }
+
+    #[test]
+    fn lineage_of_local_clients() {
+        /// Returns the lineage of `pid` once it has logged over a connection, whose client end is
+        /// `client` and server end is `server`.
+        fn lineage(pid: Pid, mut client: impl Write, server: impl ClientStream) -> Pid {
+            let app = Arc::new(RwLock::new(App::new()));
+            let handle = {
+                let app = app.clone();
+                std::thread::spawn(move || handle_stream(server, 0, app))
+            };
+            client.write_all(&logger_wire::handshake()).unwrap();
+            client
+                .write_all(&log_frame(pid.as_raw(), 1, b"lineage"))
+                .unwrap();
+            drop(client);
+            join(handle);
+            let app = app.read().unwrap();
+            app.processes[app.process_id_map[&pid]].lineage
         }
-        if app.thread.selected().is_none() {
-            app.process.select(Some(id));
-            app.thread.select(Some(0));
+
+        // This process, which has a parent to be grouped by.
+        let pid = Pid::from_raw(i32::try_from(std::process::id()).unwrap());
+        let (client, server) = UnixStream::pair().unwrap();
+        assert_eq!(lineage(pid, client, server), flapping::lineage(pid));
+        // The same process id from another machine is not that of this process.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        assert_eq!(lineage(pid, client, server), pid);
+    }
+
+    #[test]
+    fn filter_level_command() {
+        let mut app = App::new();
+        for level in [log::Level::Error, log::Level::Warn, log::Level::Info] {
+            let mut log = info(level.as_str());
+            log.level = level;
+            app.add_log(Pid::from_raw(1), 2, log);
+        }
+
+        assert_eq!(command::run(&mut app, "filter level warn"), Ok(None));
+        assert_eq!(app.display_level, log::Level::Warn);
+        assert_eq!(visible_messages(&app), ["ERROR", "WARN"]);
+        // The same level as pressing `+` from `TRACE` until only warnings and errors are shown.
+        let mut pressed = App::new();
+        for _ in 0..3 {
+            pressed.shift_display_level(true);
+        }
+        assert_eq!(pressed.display_level, app.display_level);
+
+        assert_eq!(
+            command::run(&mut app, "filter level loud"),
+            Err(String::from("Unknown level `loud`"))
+        );
+        assert_eq!(
+            command::run(&mut app, "filter some text"),
+            Err(String::from("Usage: filter [TEXT] | level <LEVEL>"))
+        );
+        assert_eq!(app.display_level, log::Level::Warn);
+        // A single word is still text to filter by.
+        assert_eq!(command::run(&mut app, "filter level"), Ok(None));
+        assert_eq!(app.filter.as_deref(), Some("level"));
+    }
+
+    #[test]
+    fn alternate_screen() {
+        assert!(has_alternate_screen(Some("xterm-256color"), false));
+        assert!(has_alternate_screen(Some("screen"), false));
+        assert!(!has_alternate_screen(Some("xterm-256color"), true));
+        // Minimal terminals.
+        assert!(!has_alternate_screen(Some("dumb"), false));
+        assert!(!has_alternate_screen(Some(""), false));
+        assert!(!has_alternate_screen(None, false));
+    }
+
+    #[test]
+    fn aligned_lines() {
+        let line = |pid, thread, log: &Log| {
+            let mut line = Vec::new();
+            export::write_aligned_line(&mut line, Pid::from_raw(pid), thread, log).unwrap();
+            String::from_utf8(line).unwrap()
+        };
+        let mut error = info("second");
+        error.level = log::Level::Error;
+        assert_eq!(
+            line(1, 0x7f00_0000_0640, &info("first")),
+            "1.000000000 INFO  1       7f0000000640 first\n"
+        );
+        // Columns wider than their width are truncated.
+        assert_eq!(
+            line(4_194_304, u64::MAX, &error),
+            "1.000000000 ERROR 4194304 fffffffffff… second\n"
+        );
+    }
+
+    #[test]
+    fn levels_by_target() {
+        use log::Level::{Debug, Error, Info, Trace, Warn};
+        let mut app = App::new();
+        for (target, level) in [
+            ("db", Trace),
+            ("db::pool", Debug),
+            ("net", Info),
+            ("net", Warn),
+            ("net", Error),
+            ("app", Debug),
+        ] {
+            let mut log = info(&format!("{target} {level}"));
+            log.target = Some(target.to_string());
+            log.level = level;
+            app.add_log(Pid::from_raw(1), 2, log);
         }
+        assert_eq!(
+            Vec::from_iter(&app.seen_targets),
+            ["app", "db", "db::pool", "net"]
+        );
+        app.display_level = Info;
+
+        // Nothing is selected while the panel is hidden.
+        app.shift_target_level(true);
+        assert!(app.target_levels.is_empty());
+        app.toggle_target_panel();
+        // "db" at trace, and "net" at error.
+        app.next_target(1);
+        app.shift_target_level(false);
+        app.shift_target_level(false);
+        app.next_target(usize::MAX);
+        app.shift_target_level(true);
+        app.shift_target_level(true);
+        assert_eq!(
+            visible_messages(&app),
+            ["db TRACE", "db::pool DEBUG", "net ERROR"]
+        );
+
+        // The most specific target nested in decides.
+        app.previous_target(1);
+        app.shift_target_level(true);
+        assert_eq!(visible_messages(&app), ["db TRACE", "net ERROR"]);
+
+        app.next_target(1);
+        app.clear_target_level();
+        assert_eq!(
+            visible_messages(&app),
+            ["db TRACE", "net INFO", "net WARN", "net ERROR"]
+        );
     }
 }
@@ -0,0 +1,138 @@
+//! Degrading colors for terminals with limited color support, such as some SSH sessions or
+//! `TERM=dumb`, and disabling them altogether with `--color never`.
+
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+use tui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+
+/// The colors the interface is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Palette {
+    /// Detects the palette from `TERM` and `COLORTERM`.
+    Auto,
+    /// Any color.
+    Full,
+    /// The 8 basic colors.
+    Basic,
+    /// No colors, only modifiers such as bold.
+    Mono,
+}
+
+/// When to use colors, given with `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Uses colors when writing to a terminal.
+    Auto,
+    /// Uses colors even when not writing to a terminal.
+    Always,
+    /// Never uses colors.
+    Never,
+}
+
+impl ColorChoice {
+    /// Returns whether output to stdout is colored.
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Auto => std::io::stdout().is_terminal(),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+impl Palette {
+    /// Resolves [`Palette::Auto`] to the palette supported by the terminal, drawing without colors
+    /// whenever `color` disables them.
+    pub fn resolve(self, color: ColorChoice) -> Self {
+        if !color.enabled() {
+            return Self::Mono;
+        }
+        if self != Self::Auto {
+            return self;
+        }
+        detect(
+            &std::env::var("TERM").unwrap_or_default(),
+            &std::env::var("COLORTERM").unwrap_or_default(),
+            color,
+        )
+    }
+
+    fn color(self, color: Color) -> Color {
+        match self {
+            Self::Auto | Self::Full => color,
+            Self::Mono => Color::Reset,
+            Self::Basic => match color {
+                Color::LightRed => Color::Red,
+                Color::LightGreen => Color::Green,
+                Color::LightYellow => Color::Yellow,
+                Color::LightBlue => Color::Blue,
+                Color::LightMagenta => Color::Magenta,
+                Color::LightCyan => Color::Cyan,
+                Color::Gray | Color::DarkGray => Color::White,
+                Color::Rgb(..) | Color::Indexed(_) => Color::Reset,
+                color => color,
+            },
+        }
+    }
+}
+
+/// Returns the palette supported by the terminal `term` and `colorterm` name, as in `TERM` and
+/// `COLORTERM`, when colors are enabled by `color`.
+fn detect(term: &str, colorterm: &str, color: ColorChoice) -> Palette {
+    if term.is_empty() || term == "dumb" {
+        // Colors were asked for, so the terminal is trusted with the most widely supported.
+        if color == ColorChoice::Always {
+            Palette::Basic
+        } else {
+            Palette::Mono
+        }
+    } else if colorterm == "truecolor" || colorterm == "24bit" || term.contains("256color") {
+        Palette::Full
+    } else {
+        Palette::Basic
+    }
+}
+
+/// Rendered over the whole frame last, replacing every color the palette does not support.
+impl Widget for Palette {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if matches!(self, Self::Auto | Self::Full) {
+            return;
+        }
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = buf.get_mut(x, y);
+                cell.fg = self.color(cell.fg);
+                cell.bg = self.color(cell.bg);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_palette() {
+        for (term, colorterm, color, expected) in [
+            ("dumb", "", ColorChoice::Auto, Palette::Mono),
+            ("dumb", "", ColorChoice::Always, Palette::Basic),
+            ("", "", ColorChoice::Auto, Palette::Mono),
+            ("", "truecolor", ColorChoice::Always, Palette::Basic),
+            ("xterm", "", ColorChoice::Auto, Palette::Basic),
+            ("linux", "", ColorChoice::Always, Palette::Basic),
+            ("xterm-256color", "", ColorChoice::Auto, Palette::Full),
+            ("screen-256color", "", ColorChoice::Auto, Palette::Full),
+            ("xterm", "truecolor", ColorChoice::Auto, Palette::Full),
+            ("xterm", "24bit", ColorChoice::Auto, Palette::Full),
+        ] {
+            assert_eq!(
+                detect(term, colorterm, color),
+                expected,
+                "TERM={term:?} COLORTERM={colorterm:?} {color:?}"
+            );
+        }
+    }
+}
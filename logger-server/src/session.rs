@@ -0,0 +1,40 @@
+//! Investigation sessions, spans of the capture such as one reproduction of a bug, started and
+//! stopped from the command palette. The log pane and reports can be limited to the logs of a
+//! session.
+
+use std::time::{Duration, SystemTime};
+
+pub struct Session {
+    pub name: String,
+    /// When the session started, since the Unix epoch like the times of logs.
+    pub start: Duration,
+    /// When the session stopped, `None` while it is running.
+    pub end: Option<Duration>,
+}
+
+impl Session {
+    /// Starts a session named `name` at the current time.
+    pub fn start(name: String) -> Self {
+        Self {
+            name,
+            start: now(),
+            end: None,
+        }
+    }
+
+    /// Stops the session at the current time.
+    pub fn stop(&mut self) {
+        self.end = Some(now());
+    }
+
+    /// Returns whether a log at `time` belongs to the session.
+    pub fn contains(&self, time: Duration) -> bool {
+        time >= self.start && self.end.is_none_or(|end| time <= end)
+    }
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+}
@@ -0,0 +1,107 @@
+//! Formatting the times of logs in the log pane, chosen with `--time-format`.
+//!
+//! Exports always keep the seconds and nanoseconds since the Unix epoch, whatever the format.
+
+use std::{mem::MaybeUninit, time::Duration};
+
+use clap::ValueEnum;
+use nix::libc;
+
+/// How the times of logs are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TimeFormat {
+    /// The time of day to the millisecond, as in `12:34:56.789`.
+    Clock,
+    /// Microseconds since the Unix epoch, as sent by clients.
+    Micros,
+    /// The date and time to the millisecond, as in `2024-01-31T12:34:56.789+01:00`.
+    Iso8601,
+    /// The time since the server started, as in `+1:02:03.456`.
+    Relative,
+}
+
+impl TimeFormat {
+    /// Returns the header of the time column of the log pane.
+    pub fn header(self) -> &'static str {
+        match self {
+            Self::Clock | Self::Iso8601 => "Time",
+            Self::Micros => "Time (μs)",
+            Self::Relative => "Since start",
+        }
+    }
+
+    /// Returns the width of the time column of the log pane.
+    pub fn width(self) -> u16 {
+        match self {
+            Self::Clock => 12,
+            Self::Micros => 16,
+            Self::Iso8601 => 29,
+            Self::Relative => 13,
+        }
+    }
+
+    /// Formats `time`, since the Unix epoch, in local time or UTC when `utc` is set. `start` is
+    /// when the server started, also since the Unix epoch.
+    pub fn format(self, time: Duration, utc: bool, start: Duration) -> String {
+        let millis = time.subsec_millis();
+        match self {
+            Self::Micros => time.as_micros().to_string(),
+            Self::Relative => match time.checked_sub(start) {
+                Some(since) => {
+                    let secs = since.as_secs();
+                    format!(
+                        "+{}:{:02}:{:02}.{:03}",
+                        secs / 3600,
+                        secs / 60 % 60,
+                        secs % 60,
+                        since.subsec_millis()
+                    )
+                }
+                // Logged before the server started, by a client whose clock is ahead.
+                None => String::from("-"),
+            },
+            Self::Clock | Self::Iso8601 => {
+                let Some(tm) = broken_down(time.as_secs(), utc) else {
+                    return time.as_micros().to_string();
+                };
+                let clock = format!(
+                    "{:02}:{:02}:{:02}.{millis:03}",
+                    tm.tm_hour, tm.tm_min, tm.tm_sec
+                );
+                if self == Self::Clock {
+                    return clock;
+                }
+                let offset = if utc || tm.tm_gmtoff == 0 {
+                    String::from("Z")
+                } else {
+                    let minutes = tm.tm_gmtoff.abs() / 60;
+                    let sign = if tm.tm_gmtoff < 0 { '-' } else { '+' };
+                    format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+                };
+                format!(
+                    "{}-{:02}-{:02}T{clock}{offset}",
+                    i64::from(tm.tm_year) + 1900,
+                    tm.tm_mon + 1,
+                    tm.tm_mday
+                )
+            }
+        }
+    }
+}
+
+/// Splits `secs` since the Unix epoch into the date and time of day, in local time or UTC when
+/// `utc` is set, or `None` when it is out of range.
+fn broken_down(secs: u64, utc: bool) -> Option<libc::tm> {
+    let secs = libc::time_t::try_from(secs).ok()?;
+    let mut tm = MaybeUninit::uninit();
+    // SAFETY: Both functions only write to `tm`, returning null when they fail to.
+    let result = unsafe {
+        if utc {
+            libc::gmtime_r(&secs, tm.as_mut_ptr())
+        } else {
+            libc::localtime_r(&secs, tm.as_mut_ptr())
+        }
+    };
+    // SAFETY: `tm` is initialized when the call succeeded.
+    (!result.is_null()).then(|| unsafe { tm.assume_init() })
+}
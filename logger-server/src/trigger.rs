@@ -0,0 +1,163 @@
+//! Running a command when a log matching a rule arrives, given with `--on-match`.
+//!
+//! A rule is one or more conditions joined by `&&`, all of which must hold:
+//!
+//! - `level<op><LEVEL>` compares severities, where `<op>` is one of `>=`, `>`, `<=`, `<`, `=` or
+//!   `!=` and more severe levels are greater, so `level>=warn` matches warnings and errors.
+//! - `msg~<TEXT>` matches messages containing `TEXT`.
+//! - `pid=<PID>` matches logs from the process `PID`.
+//!
+//! The command is run with the fields of the matching log in the environment variables
+//! `MP_LOGGER_PID`, `MP_LOGGER_THREAD`, `MP_LOGGER_LEVEL`, `MP_LOGGER_TIME` and
+//! `MP_LOGGER_MESSAGE`.
+
+use std::{
+    cmp::Ordering,
+    process::{Command, Stdio},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use nix::{sys::pthread::Pthread, unistd::Pid};
+
+use crate::Log;
+
+/// A condition on a log.
+#[derive(Debug, Clone)]
+enum Condition {
+    /// The severity of the log compared to a level is one of the orderings.
+    Level(log::Level, &'static [Ordering]),
+    Message(String),
+    Pid(Pid),
+}
+
+impl Condition {
+    fn matches(&self, pid: Pid, log: &Log) -> bool {
+        match self {
+            // More severe levels compare as less in `log`, so the comparison is reversed.
+            Self::Level(level, orderings) => orderings.contains(&level.cmp(&log.level)),
+            Self::Message(text) => log.message.contains(text.as_str()),
+            Self::Pid(expected) => pid == *expected,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(condition: &str) -> Result<Self, Self::Err> {
+        let condition = condition.trim();
+        if let Some(level) = condition.strip_prefix("level") {
+            const OPERATORS: [(&str, &[Ordering]); 6] = [
+                (">=", &[Ordering::Greater, Ordering::Equal]),
+                ("<=", &[Ordering::Less, Ordering::Equal]),
+                ("!=", &[Ordering::Greater, Ordering::Less]),
+                (">", &[Ordering::Greater]),
+                ("<", &[Ordering::Less]),
+                ("=", &[Ordering::Equal]),
+            ];
+            let (level, orderings) = OPERATORS
+                .iter()
+                .find_map(|&(operator, orderings)| {
+                    level.strip_prefix(operator).map(|level| (level, orderings))
+                })
+                .ok_or_else(|| format!("expected a comparison after `level` in `{condition}`"))?;
+            let level = level
+                .trim()
+                .parse()
+                .map_err(|_| format!("unknown level in `{condition}`"))?;
+            Ok(Self::Level(level, orderings))
+        } else if let Some(text) = condition.strip_prefix("msg~") {
+            Ok(Self::Message(text.to_string()))
+        } else if let Some(pid) = condition.strip_prefix("pid=") {
+            let pid = pid
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid process id in `{condition}`"))?;
+            Ok(Self::Pid(Pid::from_raw(pid)))
+        } else {
+            Err(format!("unknown condition `{condition}`"))
+        }
+    }
+}
+
+/// Conditions which must all hold for a log to match.
+#[derive(Debug, Clone)]
+pub struct Rule(Vec<Condition>);
+
+impl Rule {
+    fn matches(&self, pid: Pid, log: &Log) -> bool {
+        self.0.iter().all(|condition| condition.matches(pid, log))
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        rule.split("&&")
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+pub struct Trigger {
+    rule: Rule,
+    /// The program and its arguments.
+    command: Vec<String>,
+    /// The minimum time between runs of the command.
+    interval: Duration,
+    last_run: Option<Instant>,
+}
+
+impl Trigger {
+    pub fn new(rule: Rule, command: Vec<String>, interval: Duration) -> Self {
+        Self {
+            rule,
+            command,
+            interval,
+            last_run: None,
+        }
+    }
+
+    /// Runs the command if `log` from the thread `thread` of the process `pid` matches the rule
+    /// and the command has not run within the interval, returning whether it was started.
+    ///
+    /// The command runs in the background with no access to the terminal.
+    ///
+    /// # Errors
+    ///
+    /// When failing to start the command.
+    pub fn fire(&mut self, pid: Pid, thread: Pthread, log: &Log) -> std::io::Result<bool> {
+        if !self.rule.matches(pid, log)
+            || self
+                .last_run
+                .is_some_and(|last_run| last_run.elapsed() < self.interval)
+        {
+            return Ok(false);
+        }
+        self.last_run = Some(Instant::now());
+
+        let Some((program, args)) = self.command.split_first() else {
+            return Ok(false);
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .env("MP_LOGGER_PID", pid.to_string())
+            .env("MP_LOGGER_THREAD", format!("{thread:x}"))
+            .env("MP_LOGGER_LEVEL", log.severity().name())
+            .env(
+                "MP_LOGGER_TIME",
+                format!("{}.{:09}", log.time.as_secs(), log.time.subsec_nanos()),
+            )
+            .env("MP_LOGGER_MESSAGE", &log.message)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // Reaps the command once it exits.
+        std::thread::spawn(move || child.wait());
+        Ok(true)
+    }
+}
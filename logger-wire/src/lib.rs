@@ -0,0 +1,439 @@
+//! The binary wire format between `logger-client` and `logger-server`.
+//!
+//! A connection opens with the handshake, the little-endian `u16` [`WIRE_VERSION`], followed by
+//! any number of frames. Each frame is a [`Header`] of [`Header::LEN`] bytes followed by a message
+//! of `length` bytes. Every integer is little-endian so the format does not depend on how either
+//! side was compiled.
+//!
+//! A message with the [`COMPRESSED`] flag is deflated, see [`compress`], and its header describes
+//! it as it is before being compressed except for `length`.
+#![warn(clippy::pedantic)]
+
+use std::{
+    error::Error,
+    fmt,
+    io::{Read, Write},
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+/// The version of the wire format, changed whenever the encoding changes.
+pub const WIRE_VERSION: u16 = 9;
+
+/// A frame carrying a log record, whose message is the target of the record, its module path, its
+/// file and then its text, split by [`Header::target_length`], [`Header::module_path_length`] and
+/// [`Header::file_length`].
+pub const LOG_FRAME: u8 = 0;
+/// A frame whose message is the little-endian `u64` total of records dropped by the client.
+pub const STATUS_FRAME: u8 = 1;
+/// A frame whose message is the little-endian `u32` sampling rate of each level, from `ERROR` to
+/// `TRACE`.
+pub const SAMPLING_FRAME: u8 = 2;
+/// A frame whose message is the correlation id attached to the following records of the sending
+/// thread, or empty to detach it.
+pub const CORRELATION_FRAME: u8 = 3;
+/// A frame whose message is the name of the sending thread, or empty when it has none. Thread ids
+/// are reused once threads exit, so it replaces the name of any earlier thread with the same id.
+pub const THREAD_NAME_FRAME: u8 = 4;
+/// A frame whose message is the key-value pairs of the following record of the sending thread,
+/// see [`encode_key_value`]. Records without key-value pairs are sent without one.
+pub const KEY_VALUES_FRAME: u8 = 5;
+/// A frame whose message is the name of the sending process, sent once after the handshake.
+pub const PROCESS_NAME_FRAME: u8 = 6;
+/// A frame whose message is the key-value pairs describing the sending process, such as its host
+/// and version, see [`encode_key_value`]. Sent once after the name of the process when it has
+/// any.
+pub const CONTEXT_FRAME: u8 = 7;
+
+/// A flag set in [`Header::flags`] when the message is compressed.
+pub const COMPRESSED: u8 = 1;
+
+/// An error decoding the wire format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The bytes are not the length of what is being decoded.
+    Length { expected: usize, found: usize },
+    /// The client speaks a different version of the wire format.
+    Version(u16),
+    /// A compressed message is not valid deflate data.
+    Compression,
+    /// A compressed message is longer than `limit` bytes once decompressed.
+    Decompressed { limit: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Length { expected, found } => {
+                write!(f, "expected {expected} bytes, found {found}")
+            }
+            Self::Version(version) => write!(
+                f,
+                "unsupported wire format version {version}, expected {WIRE_VERSION}"
+            ),
+            Self::Compression => write!(f, "invalid compressed message"),
+            Self::Decompressed { limit } => {
+                write!(f, "compressed message longer than {limit} bytes")
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Returns the handshake a client opens a connection with.
+#[must_use]
+pub fn handshake() -> [u8; 2] {
+    WIRE_VERSION.to_le_bytes()
+}
+
+/// Checks the handshake a connection opened with.
+///
+/// # Errors
+///
+/// When the client speaks a different version of the wire format ([`DecodeError::Version`]).
+pub fn check_handshake(handshake: [u8; 2]) -> Result<(), DecodeError> {
+    match u16::from_le_bytes(handshake) {
+        WIRE_VERSION => Ok(()),
+        version => Err(DecodeError::Version(version)),
+    }
+}
+
+/// Compresses `message` to be sent with the [`COMPRESSED`] flag.
+///
+/// # Panics
+///
+/// Never, compressing into a `Vec` cannot fail.
+#[must_use]
+pub fn compress(message: &[u8]) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+    // Writing to a `Vec` cannot fail.
+    encoder.write_all(message).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Decompresses a message sent with the [`COMPRESSED`] flag, failing once it grows past `limit`
+/// bytes so a small message cannot expand to any size.
+///
+/// # Errors
+///
+/// When the message is not valid deflate data ([`DecodeError::Compression`]) or is longer than
+/// `limit` bytes once decompressed ([`DecodeError::Decompressed`]).
+pub fn decompress(message: &[u8], limit: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut decompressed = Vec::new();
+    DeflateDecoder::new(message)
+        .take(u64::try_from(limit).unwrap_or(u64::MAX).saturating_add(1))
+        .read_to_end(&mut decompressed)
+        .map_err(|_| DecodeError::Compression)?;
+    if decompressed.len() > limit {
+        return Err(DecodeError::Decompressed { limit });
+    }
+    Ok(decompressed)
+}
+
+/// Appends the key-value pair `key` and `value` to the message of a [`KEY_VALUES_FRAME`], each
+/// as its little-endian `u32` length followed by its bytes.
+///
+/// # Panics
+///
+/// When `key` or `value` is longer than `u32::MAX` bytes.
+pub fn encode_key_value(message: &mut Vec<u8>, key: &str, value: &str) {
+    for field in [key, value] {
+        let length = u32::try_from(field.len()).expect("key-value field too long");
+        message.extend_from_slice(&length.to_le_bytes());
+        message.extend_from_slice(field.as_bytes());
+    }
+}
+
+/// Decodes the key-value pairs of the message of a [`KEY_VALUES_FRAME`], replacing invalid UTF-8.
+///
+/// # Errors
+///
+/// When the message ends part way through a pair ([`DecodeError::Length`]).
+pub fn decode_key_values(mut message: &[u8]) -> Result<Vec<(String, String)>, DecodeError> {
+    let mut pairs = Vec::new();
+    while !message.is_empty() {
+        pairs.push((
+            key_value_field(&mut message)?,
+            key_value_field(&mut message)?,
+        ));
+    }
+    Ok(pairs)
+}
+
+/// Decodes the key or value at the start of `message`, advancing `message` past it.
+fn key_value_field(message: &mut &[u8]) -> Result<String, DecodeError> {
+    let truncated = |expected| DecodeError::Length {
+        expected,
+        found: message.len(),
+    };
+    let (length, rest) = message.split_first_chunk::<4>().ok_or(truncated(4))?;
+    let length = usize::try_from(u32::from_le_bytes(*length)).unwrap_or(usize::MAX);
+    if rest.len() < length {
+        return Err(truncated(length.saturating_add(4)));
+    }
+    let (field, rest) = rest.split_at(length);
+    *message = rest;
+    Ok(String::from_utf8_lossy(field).into_owned())
+}
+
+/// The fixed size start of every frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Whole seconds since the Unix epoch.
+    pub secs: u64,
+    /// Nanoseconds past `secs`.
+    pub nanos: u32,
+    /// The number of frames the sending process sent before this one, ordering frames sent at
+    /// the same time.
+    pub sequence: u64,
+    /// The id of the sending process.
+    pub pid: i32,
+    /// The id of the sending thread.
+    pub pthread: u64,
+    /// The number of bytes in the message following the header.
+    pub length: u64,
+    /// The number of bytes at the start of the message of a [`LOG_FRAME`] which are the target of
+    /// the record.
+    pub target_length: u16,
+    /// The number of bytes following the target in the message of a [`LOG_FRAME`] which are the
+    /// module path of the record, `0` when it has none.
+    pub module_path_length: u16,
+    /// The number of bytes following the module path in the message of a [`LOG_FRAME`] which are
+    /// the file of the record, `0` when it has none.
+    pub file_length: u16,
+    /// The line of the record in its file, `0` when it has none.
+    pub line: u32,
+    /// The discriminant of the `log::Level`, kept as a number since a misbehaving client may send
+    /// any value.
+    pub level: u8,
+    /// What the frame carries, one of [`LOG_FRAME`], [`STATUS_FRAME`], [`SAMPLING_FRAME`],
+    /// [`CORRELATION_FRAME`], [`THREAD_NAME_FRAME`], [`KEY_VALUES_FRAME`], [`PROCESS_NAME_FRAME`]
+    /// or [`CONTEXT_FRAME`].
+    pub kind: u8,
+    /// Flags describing the message, such as [`COMPRESSED`].
+    pub flags: u8,
+}
+
+impl Header {
+    /// The number of bytes in an encoded header.
+    pub const LEN: usize = 8 + 4 + 8 + 4 + 8 + 8 + 2 + 2 + 2 + 4 + 1 + 1 + 1;
+
+    /// Encodes the header.
+    #[must_use]
+    pub fn encode(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        let fields = [
+            &self.secs.to_le_bytes()[..],
+            &self.nanos.to_le_bytes(),
+            &self.sequence.to_le_bytes(),
+            &self.pid.to_le_bytes(),
+            &self.pthread.to_le_bytes(),
+            &self.length.to_le_bytes(),
+            &self.target_length.to_le_bytes(),
+            &self.module_path_length.to_le_bytes(),
+            &self.file_length.to_le_bytes(),
+            &self.line.to_le_bytes(),
+            &[self.level, self.kind, self.flags],
+        ];
+        let mut at = 0;
+        for field in fields {
+            bytes[at..at + field.len()].copy_from_slice(field);
+            at += field.len();
+        }
+        bytes
+    }
+
+    /// Decodes a header from `bytes`.
+    ///
+    /// # Errors
+    ///
+    /// When `bytes` is not [`Header::LEN`] bytes long ([`DecodeError::Length`]).
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let bytes = <&[u8; Self::LEN]>::try_from(bytes).map_err(|_| DecodeError::Length {
+            expected: Self::LEN,
+            found: bytes.len(),
+        })?;
+        let mut at = 0;
+        Ok(Self {
+            secs: u64::from_le_bytes(field(bytes, &mut at)),
+            nanos: u32::from_le_bytes(field(bytes, &mut at)),
+            sequence: u64::from_le_bytes(field(bytes, &mut at)),
+            pid: i32::from_le_bytes(field(bytes, &mut at)),
+            pthread: u64::from_le_bytes(field(bytes, &mut at)),
+            length: u64::from_le_bytes(field(bytes, &mut at)),
+            target_length: u16::from_le_bytes(field(bytes, &mut at)),
+            module_path_length: u16::from_le_bytes(field(bytes, &mut at)),
+            file_length: u16::from_le_bytes(field(bytes, &mut at)),
+            line: u32::from_le_bytes(field(bytes, &mut at)),
+            level: u8::from_le_bytes(field(bytes, &mut at)),
+            kind: u8::from_le_bytes(field(bytes, &mut at)),
+            flags: u8::from_le_bytes(field(bytes, &mut at)),
+        })
+    }
+}
+
+/// Returns the `N` bytes of `bytes` starting at `at`, advancing `at` past them.
+fn field<const N: usize>(bytes: &[u8; Header::LEN], at: &mut usize) -> [u8; N] {
+    let mut field = [0; N];
+    field.copy_from_slice(&bytes[*at..*at + N]);
+    *at += N;
+    field
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Header {
+        Header {
+            secs: 1_700_000_000,
+            nanos: 999_999_999,
+            sequence: u64::MAX,
+            pid: -1,
+            pthread: 0x0123_4567_89ab_cdef,
+            length: 42,
+            target_length: 3,
+            module_path_length: 5,
+            file_length: 7,
+            line: 11,
+            level: 5,
+            kind: KEY_VALUES_FRAME,
+            flags: COMPRESSED,
+        }
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let header = header();
+        let bytes = header.encode();
+        assert_eq!(bytes.len(), Header::LEN);
+        assert_eq!(Header::decode(&bytes), Ok(header));
+    }
+
+    #[test]
+    fn header_truncated() {
+        let bytes = header().encode();
+        assert_eq!(
+            Header::decode(&bytes[..Header::LEN - 1]),
+            Err(DecodeError::Length {
+                expected: Header::LEN,
+                found: Header::LEN - 1
+            })
+        );
+        assert_eq!(
+            Header::decode(&[]),
+            Err(DecodeError::Length {
+                expected: Header::LEN,
+                found: 0
+            })
+        );
+    }
+
+    #[test]
+    fn handshake_round_trip() {
+        assert_eq!(check_handshake(handshake()), Ok(()));
+    }
+
+    #[test]
+    fn handshake_bad_version() {
+        let version = WIRE_VERSION.wrapping_add(1);
+        assert_eq!(
+            check_handshake(version.to_le_bytes()),
+            Err(DecodeError::Version(version))
+        );
+    }
+
+    #[test]
+    fn key_values_round_trip() {
+        let pairs = [("user", "42"), ("", ""), ("emoji", "🦀")];
+        let mut message = Vec::new();
+        for (key, value) in pairs {
+            encode_key_value(&mut message, key, value);
+        }
+        let decoded = decode_key_values(&message).unwrap();
+        assert_eq!(
+            decoded,
+            pairs.map(|(key, value)| (String::from(key), String::from(value)))
+        );
+        assert_eq!(decode_key_values(&[]), Ok(Vec::new()));
+    }
+
+    /// Returns `len` bytes which deflate cannot make smaller.
+    fn incompressible(len: usize) -> Vec<u8> {
+        // xorshift, any sequence without repeats will do.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state.to_le_bytes()[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compression_round_trip() {
+        for message in [
+            Vec::new(),
+            b"short".to_vec(),
+            b"repeated ".repeat(1000),
+            incompressible(4096),
+        ] {
+            let compressed = compress(&message);
+            assert_eq!(decompress(&compressed, message.len()), Ok(message));
+        }
+        assert!(compress(&b"repeated ".repeat(1000)).len() < 9000);
+        assert!(compress(&incompressible(4096)).len() >= 4096);
+    }
+
+    #[test]
+    fn decompress_corrupt() {
+        assert_eq!(
+            decompress(&[0xff; 64], usize::MAX),
+            Err(DecodeError::Compression)
+        );
+        // Truncated part way through, what was decompressed before the end may be returned.
+        let message = incompressible(4096);
+        let compressed = compress(&message);
+        assert_ne!(
+            decompress(&compressed[..compressed.len() / 2], usize::MAX),
+            Ok(message)
+        );
+    }
+
+    #[test]
+    fn decompress_limit() {
+        let message = b"repeated ".repeat(1000);
+        let compressed = compress(&message);
+        assert_eq!(
+            decompress(&compressed, message.len() - 1),
+            Err(DecodeError::Decompressed {
+                limit: message.len() - 1
+            })
+        );
+        assert_eq!(decompress(&compressed, message.len()), Ok(message));
+    }
+
+    #[test]
+    fn key_values_truncated() {
+        let mut message = Vec::new();
+        encode_key_value(&mut message, "key", "value");
+        // Part way through the length of the key.
+        assert!(matches!(
+            decode_key_values(&message[..2]),
+            Err(DecodeError::Length { expected: 4, .. })
+        ));
+        // Part way through the value.
+        assert!(matches!(
+            decode_key_values(&message[..message.len() - 1]),
+            Err(DecodeError::Length { .. })
+        ));
+        // Missing the value.
+        assert!(matches!(
+            decode_key_values(&message[..4 + 3]),
+            Err(DecodeError::Length { expected: 4, .. })
+        ));
+    }
+}
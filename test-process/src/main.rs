@@ -1,22 +1,58 @@
-use log::LevelFilter;
+use log::{Level, LevelFilter};
 use logger_client::Logger;
 use std::thread::sleep;
 use std::time::Duration;
 
 const SPACING: Duration = Duration::from_millis(100);
+const THREADS: u64 = 10;
+const ITERATIONS: u64 = 5;
+/// One in how many debug records are sent.
+const DEBUG_SAMPLING: u32 = 5;
+/// The number of records kept in memory.
+const RECENT: usize = 8;
+/// The number of bytes of records buffered before they are sent.
+const BUFFER: usize = 4096;
+/// The number of records queued to be sent in the background.
+const QUEUE: usize = 1024;
+
 fn main() {
     Logger::init("./a-local-socket", LevelFilter::Debug).unwrap();
+    Logger::sample(Level::Debug, DEBUG_SAMPLING);
+    Logger::keep_recent(RECENT);
+    Logger::buffer_writes(BUFFER);
+    Logger::send_in_background(QUEUE);
 
-    let handles = (0..10)
-        .map(|_| std::thread::spawn(tester))
+    let handles = (0..THREADS)
+        .map(|i| {
+            std::thread::Builder::new()
+                .name(format!("tester-{i}"))
+                .spawn(tester)
+                .unwrap()
+        })
         .collect::<Vec<_>>();
     for handle in handles {
         handle.join().unwrap();
     }
+
+    // Sampling is deterministic so exactly 4 in 5 debug records are sampled out.
+    let debug = THREADS * ITERATIONS;
+    let sent = debug.div_ceil(u64::from(DEBUG_SAMPLING));
+    assert_eq!(Logger::sampled_out_records(), debug - sent);
+
+    // A run of empty messages is framed like any other, each shown as `<empty>`.
+    for _ in 0..3 {
+        log::info!("");
+    }
+
+    // Sampled out records are kept too, so there are more than enough to fill the buffer.
+    assert_eq!(Logger::recent_logs().len(), RECENT);
+
+    // Sends the records still buffered or queued, then closes the connection.
+    Logger::shutdown();
 }
 
 fn tester() {
-    for _ in 0..5 {
+    for _ in 0..ITERATIONS {
         log::trace!("test trace");
         sleep(SPACING);
         log::debug!("test debug");
@@ -27,5 +63,9 @@ fn tester() {
         sleep(SPACING);
         log::error!("test error");
         sleep(SPACING);
+        log::error!(target: "auth", "test denied");
+        sleep(SPACING);
+        log::info!(user = 42, request = "login"; "test fields");
+        sleep(SPACING);
     }
 }